@@ -0,0 +1,38 @@
+//! Counts the allocations made when cloning a [`MetricsMiddleware`] with every option that owns
+//! its value set. Its configuration is shared between clones, so a clone is a single reference
+//! count bump: no allocations, whatever the configuration.
+//!
+//! Run with `cargo bench --bench clone`.
+
+mod common;
+
+use std::hint::black_box;
+
+use http::{HeaderName, StatusCode};
+use reqwest_metrics::MetricsMiddleware;
+
+fn main() {
+    let middleware = MetricsMiddleware::builder()
+        .http_request_method_label("method")
+        .http_response_status_label("status")
+        .server_address_label("host")
+        .request_duration_metric("internal.http.client.request.duration")
+        .request_duration_description("Duration of internal HTTP client requests.")
+        .constant_label("env", "production")
+        .peer_service("payments")
+        .client_name("payments-client")
+        .network_protocol_name_value("grpc")
+        .duration_buckets(vec![0.1, 0.25, 0.5, 1.0])
+        .request_body_size_buckets(vec![1024.0, 65536.0])
+        .response_body_size_buckets(vec![1024.0, 65536.0])
+        .error_status_excludes([StatusCode::SERVICE_UNAVAILABLE])
+        .server_address_from_header(HeaderName::from_static("x-upstream-host"))
+        .server_port_from_header(HeaderName::from_static("x-upstream-port"))
+        .max_address_cardinality(100)
+        .build();
+
+    println!("size: {} bytes", std::mem::size_of::<MetricsMiddleware>());
+    common::measure("clone", || {
+        black_box(black_box(&middleware).clone());
+    });
+}
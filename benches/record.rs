@@ -0,0 +1,34 @@
+//! Counts the allocations made when recording a request with the default and custom label names.
+//!
+//! Run with `cargo bench --bench record`.
+
+mod common;
+
+use std::{hint::black_box, time::Duration};
+
+use reqwest_metrics::MetricsMiddleware;
+use reqwest_middleware::reqwest;
+
+fn bench(name: &str, middleware: &MetricsMiddleware) {
+    let req = reqwest::Client::new()
+        .get("https://example.com/hello")
+        .build()
+        .unwrap();
+    let res = Ok(reqwest::Response::from(http::Response::new("")));
+
+    common::measure(name, || {
+        black_box(middleware).record(&req, &res, Duration::from_millis(10));
+    });
+}
+
+fn main() {
+    bench("default label names", &MetricsMiddleware::new());
+    bench(
+        "custom label names",
+        &MetricsMiddleware::builder()
+            .http_request_method_label("method")
+            .http_response_status_label("status")
+            .server_address_label("host")
+            .build(),
+    );
+}
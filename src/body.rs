@@ -0,0 +1,101 @@
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use bytes::Bytes;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use reqwest_middleware::reqwest::{Body, Response, ResponseBuilderExt};
+
+use crate::{recording::HistogramHandle, BodySizeUnit};
+
+/// Wraps the body of `res` so the number of bytes read is recorded to `histogram`, in `unit`, once
+/// the body has been fully consumed.
+pub(crate) fn measure_response_body(
+    res: Response,
+    histogram: HistogramHandle,
+    unit: BodySizeUnit,
+) -> Response {
+    let url = res.url().clone();
+    let (parts, body) = http::Response::<Body>::from(res).into_parts();
+
+    let mut builder = http::Response::builder()
+        .status(parts.status)
+        .version(parts.version)
+        .url(url);
+    if let Some(headers) = builder.headers_mut() {
+        *headers = parts.headers;
+    }
+    if let Some(extensions) = builder.extensions_mut() {
+        extensions.extend(parts.extensions);
+    }
+
+    builder
+        .body(Body::wrap(CountingBody::new(body, histogram, unit)))
+        .expect("response parts are already valid")
+        .into()
+}
+
+/// A body wrapper that counts the bytes flowing through it.
+struct CountingBody<B> {
+    inner: B,
+    size: u64,
+    histogram: Option<HistogramHandle>,
+    unit: BodySizeUnit,
+}
+
+impl<B> CountingBody<B> {
+    fn new(inner: B, histogram: HistogramHandle, unit: BodySizeUnit) -> Self {
+        Self {
+            inner,
+            size: 0,
+            histogram: Some(histogram),
+            unit,
+        }
+    }
+
+    fn record(&mut self) {
+        if let Some(histogram) = self.histogram.take() {
+            histogram.record(self.unit.value(self.size));
+        }
+    }
+}
+
+impl<B> HttpBody for CountingBody<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    this.size += data.len() as u64;
+                }
+                if this.inner.is_end_stream() {
+                    this.record();
+                }
+            }
+            Some(Err(_)) => {}
+            None => this.record(),
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
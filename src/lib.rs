@@ -16,6 +16,12 @@ let client = ClientBuilder::new(reqwest::Client::new())
     .build();
 ```
 
+Or, when no other middlewares are needed, with [`instrumented_client`]:
+
+```rust
+let client = reqwest_metrics::instrumented_client(reqwest::Client::new());
+```
+
 ## Configuration
 
 ### Overriding label names
@@ -34,10 +40,58 @@ let client = ClientBuilder::new(reqwest::Client::new())
     .build();
 ```
 
+### Overriding metric names
+
+```rust
+# use reqwest_middleware::ClientBuilder;
+# use reqwest_metrics::MetricsMiddleware;
+let client = ClientBuilder::new(reqwest::Client::new())
+    .with(
+        MetricsMiddleware::builder()
+            .request_duration_metric("internal.http.client.request.duration")
+            .build(),
+    )
+    .build();
+```
+
+### Tower
+
+With the `tower` feature enabled, `MetricsLayer` can be used to emit the same metrics from any
+`tower` based HTTP client.
+
+```rust,ignore
+let service = tower::ServiceBuilder::new()
+    .layer(MetricsLayer::new(MetricsMiddleware::new()))
+    .service(client);
+```
+
+### WebAssembly
+
+On `wasm32` targets, body sizes are read from the request body and the `content-length` header
+of responses. The size of multipart request bodies is unknown, since only the browser knows it.
+
 Supported metrics:
 * [`http.client.request.duration`](https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpclientrequestduration)
 * [`http.client.request.body.size`](https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpclientrequestbodysize)
 * [`http.client.response.body.size`](https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpclientresponsebodysize)
+* [`http.client.active_requests`](https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpclientactive_requests) (opt-in)
+* [`http.client.connection.duration`](https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpclientconnectionduration) (opt-in)
+* `http.client.dns.duration` (opt-in)
+* `http.client.request.count`
+* `http.client.redirect.count` (opt-in)
+* `http.client.request.timeouts` (opt-in)
+* `http.client.request.last_duration` (opt-in)
+* `http.client.connect.duration` (opt-in)
+* `http.client.request.attempts` (opt-in)
+* `http.client.request.attempt.duration` (opt-in)
+* `http.client.request.duration.total` (opt-in)
+* `http.client.request.started` (opt-in)
+* `http.client.request.slow.count` (opt-in)
+* `http.client.request.header.count` (opt-in)
+* `http.client.response.header.count` (opt-in)
+* `http.client.response.time_to_first_byte` (opt-in)
+* `http.client.upgrade.duration` (opt-in)
+* `http.client.response.count` (opt-in)
 
 Supported labels:
 * `http_request_method`
@@ -45,9 +99,25 @@ Supported labels:
 * `server_port`
 * `error_type`
 * `http_response_status_code`
+* `http_response_status_class` (opt-in)
 * `network_protocol_name`
-* `network_protocol_version`
+* `network_protocol_version` (best-effort, not available on `wasm32`)
+* `network_transport` (opt-in)
 * `url_scheme`
+* `http_route` (opt-in)
+* `url_full` (opt-in)
+* `cache_hit` (opt-in)
+* `outcome` (opt-in, `http.client.request.count` only)
+* `connection_reused` (opt-in)
+* `operation` (opt-in)
+* `tls_protocol_version` (opt-in, when a [`TlsVersion`] is present)
+* `http_client_name` (opt-in)
+* `user_agent_original` (opt-in)
+* `request_class` (when a `RequestClass` is present)
+* `peer_service` (opt-in)
+* `http_request_resend_count` (when a [`ResendCount`] is present)
+* `http_response_header_content_type` (opt-in)
+* `trace_id` (opt-in, requires the `otel-trace-id` feature)
 
 ## Motivation
 
@@ -58,95 +128,1357 @@ This crate is heavily inspired by the [HTTP Client metrics](https://docs.spring.
 
 #![deny(missing_docs)]
 
-use std::{borrow::Cow, time::Instant};
+#[cfg(not(target_arch = "wasm32"))]
+mod body;
+mod recording;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "tower")]
+mod tower;
+
+pub use recording::RecordingMode;
+#[cfg(feature = "tower")]
+pub use tower::{MetricsLayer, MetricsService, ResponseFuture};
 
-use http::{Extensions, Method};
-use metrics::{describe_histogram, histogram, Unit};
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::{hash_map::RandomState, HashSet},
+    fmt,
+    future::{poll_fn, Future},
+    hash::{BuildHasher, Hasher},
+    mem,
+    net::IpAddr,
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
+    pin::pin,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use http::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE, UPGRADE, USER_AGENT},
+    Extensions, HeaderMap, HeaderName, Method, StatusCode,
+};
+use label_names::*;
+use metric_names::*;
+use metrics::{Counter, Gauge, Histogram, Key, Level, Metadata, Unit};
+use recording::{describe_once, HistogramHandle, MetricKind};
 use reqwest_middleware::{
-    reqwest::{Request, Response},
-    Error, Middleware, Next, Result,
+    reqwest::{Request, Response, Url},
+    ClientBuilder, ClientWithMiddleware, Error, Middleware, Next, Result,
 };
 
-// Defaults should follow Open Telemetry when possible
-// https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#http-client
-const HTTP_CLIENT_REQUEST_DURATION: &str = "http.client.request.duration";
-const HTTP_CLIENT_REQUEST_BODY_SIZE: &str = "http.client.request.body.size";
-const HTTP_CLIENT_RESPONSE_BODY_SIZE: &str = "http.client.response.body.size";
-// Labels
-const HTTP_REQUEST_METHOD: &str = "http.request.method";
-const SERVER_ADDRESS: &str = "server.address";
-const SERVER_PORT: &str = "server.port";
-const ERROR_TYPE: &str = "error.type";
-const HTTP_RESPONSE_STATUS_CODE: &str = "http.response.status_code";
-const NETWORK_PROTOCOL_NAME: &str = "network.protocol.name";
-const NETWORK_PROTOCOL_VERSION: &str = "network.protocol.version";
-const URL_SCHEME: &str = "url.scheme";
+/// The default names of the metrics recorded by [`MetricsMiddleware`].
+///
+/// [`MetricsMiddleware`]: crate::MetricsMiddleware
+pub mod metric_names {
+    // Defaults should follow Open Telemetry when possible
+    // https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#http-client
+    /// The `http.client.request.duration` metric.
+    pub const HTTP_CLIENT_REQUEST_DURATION: &str = "http.client.request.duration";
+    /// The `http.client.request.body.size` metric.
+    pub const HTTP_CLIENT_REQUEST_BODY_SIZE: &str = "http.client.request.body.size";
+    /// The `http.client.response.body.size` metric.
+    pub const HTTP_CLIENT_RESPONSE_BODY_SIZE: &str = "http.client.response.body.size";
+    /// The `http.client.active_requests` metric.
+    pub const HTTP_CLIENT_ACTIVE_REQUESTS: &str = "http.client.active_requests";
+    /// The `http.client.connection.duration` metric.
+    pub const HTTP_CLIENT_CONNECTION_DURATION: &str = "http.client.connection.duration";
+    /// The `http.client.dns.duration` metric.
+    pub const HTTP_CLIENT_DNS_DURATION: &str = "http.client.dns.duration";
+    // Not part of the OTel spec, but useful for backends that do not emit a histogram `_count` series
+    /// The `http.client.request.count` metric.
+    pub const HTTP_CLIENT_REQUEST_COUNT: &str = "http.client.request.count";
+    /// The `http.client.redirect.count` metric.
+    pub const HTTP_CLIENT_REDIRECT_COUNT: &str = "http.client.redirect.count";
+    /// The `http.client.request.timeouts` metric.
+    pub const HTTP_CLIENT_REQUEST_TIMEOUTS: &str = "http.client.request.timeouts";
+    /// The `http.client.request.last_duration` metric.
+    pub const HTTP_CLIENT_REQUEST_LAST_DURATION: &str = "http.client.request.last_duration";
+    /// The `http.client.connect.duration` metric.
+    pub const HTTP_CLIENT_CONNECT_DURATION: &str = "http.client.connect.duration";
+    /// The `http.client.request.attempts` metric.
+    pub const HTTP_CLIENT_REQUEST_ATTEMPTS: &str = "http.client.request.attempts";
+    /// The `http.client.request.attempt.duration` metric.
+    pub const HTTP_CLIENT_REQUEST_ATTEMPT_DURATION: &str = "http.client.request.attempt.duration";
+    /// The `http.client.request.duration.total` metric.
+    pub const HTTP_CLIENT_REQUEST_DURATION_TOTAL: &str = "http.client.request.duration.total";
+    /// The `http.client.request.started` metric.
+    pub const HTTP_CLIENT_REQUEST_STARTED: &str = "http.client.request.started";
+    /// The `http.client.request.slow.count` metric.
+    pub const HTTP_CLIENT_REQUEST_SLOW_COUNT: &str = "http.client.request.slow.count";
+    /// The `http.client.request.header.count` metric.
+    pub const HTTP_CLIENT_REQUEST_HEADER_COUNT: &str = "http.client.request.header.count";
+    /// The `http.client.response.header.count` metric.
+    pub const HTTP_CLIENT_RESPONSE_HEADER_COUNT: &str = "http.client.response.header.count";
+    /// The `http.client.response.time_to_first_byte` metric.
+    pub const HTTP_CLIENT_RESPONSE_TIME_TO_FIRST_BYTE: &str =
+        "http.client.response.time_to_first_byte";
+    /// The `http.client.upgrade.duration` metric.
+    pub const HTTP_CLIENT_UPGRADE_DURATION: &str = "http.client.upgrade.duration";
+    /// The `http.client.response.count` metric.
+    pub const HTTP_CLIENT_RESPONSE_COUNT: &str = "http.client.response.count";
+}
+
+/// The default names of the labels recorded by [`MetricsMiddleware`].
+///
+/// [`MetricsMiddleware`]: crate::MetricsMiddleware
+pub mod label_names {
+    /// The `http.request.method` label.
+    pub const HTTP_REQUEST_METHOD: &str = "http.request.method";
+    /// The `server.address` label.
+    pub const SERVER_ADDRESS: &str = "server.address";
+    /// The `server.port` label.
+    pub const SERVER_PORT: &str = "server.port";
+    /// The `error.type` label.
+    pub const ERROR_TYPE: &str = "error.type";
+    /// The `http.response.status_code` label.
+    pub const HTTP_RESPONSE_STATUS_CODE: &str = "http.response.status_code";
+    /// The `http.response.status_class` label.
+    pub const HTTP_RESPONSE_STATUS_CLASS: &str = "http.response.status_class";
+    /// The `network.protocol.name` label.
+    pub const NETWORK_PROTOCOL_NAME: &str = "network.protocol.name";
+    /// The `network.protocol.version` label.
+    pub const NETWORK_PROTOCOL_VERSION: &str = "network.protocol.version";
+    /// The `url.scheme` label.
+    pub const URL_SCHEME: &str = "url.scheme";
+    /// The `http.route` label.
+    pub const HTTP_ROUTE: &str = "http.route";
+    /// The `peer.service` label.
+    pub const PEER_SERVICE: &str = "peer.service";
+    /// The `http.request.resend_count` label.
+    pub const HTTP_REQUEST_RESEND_COUNT: &str = "http.request.resend_count";
+    /// The `http.response.header.content-type` label.
+    pub const HTTP_RESPONSE_HEADER_CONTENT_TYPE: &str = "http.response.header.content-type";
+    /// The `trace_id` label.
+    pub const TRACE_ID: &str = "trace_id";
+    /// The `network.transport` label.
+    pub const NETWORK_TRANSPORT: &str = "network.transport";
+    /// The `url.full` label.
+    pub const URL_FULL: &str = "url.full";
+    /// The `cache.hit` label.
+    pub const CACHE_HIT: &str = "cache.hit";
+    /// The `outcome` label.
+    pub const OUTCOME: &str = "outcome";
+    /// The `connection.reused` label.
+    pub const CONNECTION_REUSED: &str = "connection.reused";
+    /// The `operation` label.
+    pub const OPERATION: &str = "operation";
+    /// The `tls.protocol.version` label.
+    pub const TLS_PROTOCOL_VERSION: &str = "tls.protocol.version";
+    /// The `http.client.name` label.
+    pub const HTTP_CLIENT_NAME: &str = "http.client.name";
+    /// The `user_agent.original` label.
+    pub const USER_AGENT_ORIGINAL: &str = "user_agent.original";
+    /// The `request.class` label.
+    pub const REQUEST_CLASS: &str = "request.class";
+}
+
+/// Wraps `client` with a default [`MetricsMiddleware`].
+///
+/// This is a shorthand for
+/// `ClientBuilder::new(client).with(MetricsMiddleware::new()).build()`, use
+/// [`ClientBuilder`] directly to combine the middleware with other middlewares.
+pub fn instrumented_client(client: reqwest_middleware::reqwest::Client) -> ClientWithMiddleware {
+    instrumented_client_with(client, MetricsMiddleware::new())
+}
+
+/// Wraps `client` with the given [`MetricsMiddleware`].
+///
+/// ```
+/// # use reqwest_metrics::{instrumented_client_with, MetricsMiddleware};
+/// let client = instrumented_client_with(
+///     reqwest::Client::new(),
+///     MetricsMiddleware::builder().enable_status_class().build(),
+/// );
+/// ```
+pub fn instrumented_client_with(
+    client: reqwest_middleware::reqwest::Client,
+    middleware: MetricsMiddleware,
+) -> ClientWithMiddleware {
+    ClientBuilder::new(client).with(middleware).build()
+}
 
 /// Middleware to handle emitting HTTP metrics for a reqwest client
-/// NOTE: Creating a `[MetricMiddleware]` will describe a histogram on construction.
+/// NOTE: Creating a `[MetricMiddleware]` will describe its metrics on construction, once per
+/// metric and recorder.
+///
+/// Requests rejected by a middleware added after this one (ie. a rate limiter) are recorded with
+/// an `error.type` of `middleware` and without an `http.response.status_code`. Requests rejected
+/// by a middleware added before this one never reach it, so they are not recorded.
+///
+/// Labels are always recorded in the same order: the constant labels, the labels of the request
+/// and response (ie. `http.request.method` before `server.address`), the labels read from the
+/// [`Extensions`] (ie. `http.request.resend_count` or `connection.reused`) and finally the custom
+/// labels of [`MetricsMiddlewareBuilder::labels_from_request`] and
+/// [`MetricsMiddlewareBuilder::dynamic_labels`]. Labels that do not apply to a request (ie.
+/// `server.port` when omitted) are skipped without changing the order of the others.
+///
+/// `http.client.request.duration` is measured until the middlewares added after this one return,
+/// which is once the response headers are received: reading the response body is not included,
+/// unless one of these middlewares buffers it (ie. a cache). See
+/// [`MetricsMiddlewareBuilder::enable_time_to_first_byte`].
 #[derive(Debug, Clone)]
 pub struct MetricsMiddleware {
+    inner: Arc<Inner>,
+}
+
+/// The configuration of a [`MetricsMiddleware`], shared between its clones so that cloning it
+/// (ie. per client) only bumps a reference count.
+#[derive(Debug)]
+struct Inner {
     label_names: LabelNames,
+    metric_names: MetricNames,
+    peer_service: Option<String>,
+    constant_labels: Vec<(String, String)>,
+    server_address_header: Option<HeaderName>,
+    server_port_header: Option<HeaderName>,
+    duration_buckets: Option<Vec<f64>>,
+    request_body_size_buckets: Option<Vec<f64>>,
+    response_body_size_buckets: Option<Vec<f64>>,
+    network_protocol_name: Option<Cow<'static, str>>,
+    error_status_excludes: HashSet<StatusCode>,
+    client_name: Option<String>,
+    duration_unit: DurationUnit,
+    body_size_unit: BodySizeUnit,
+    active_requests: bool,
+    request_body_size: bool,
+    response_body_size: bool,
+    status_class: bool,
+    url_template: Option<Hook<UrlTemplateFn>>,
+    dynamic_labels: Option<Hook<DynamicLabelsFn>>,
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    measure_response_body: bool,
+    skip_unknown_request_body_size: bool,
+    resend_count: Option<Hook<ResendCountFn>>,
+    connection_duration: bool,
+    recording_mode: RecordingMode,
+    gauge_summary_interval: Duration,
+    response_content_type: bool,
+    #[cfg_attr(not(feature = "otel-trace-id"), allow(dead_code))]
+    trace_id: bool,
+    skip_if: Option<Hook<SkipFn>>,
+    treat_4xx_as_error: bool,
+    network_transport: bool,
+    record_request_size_eagerly: bool,
+    omit_default_port: bool,
+    redirect_count: bool,
+    clock: Hook<dyn Clock>,
+    sink: Hook<dyn MetricsSink>,
+    collapse_unknown_methods: bool,
+    labels_from_request: Option<Hook<RequestLabelsFn>>,
+    url_full: bool,
+    url_redactor: Option<Hook<UrlRedactorFn>>,
+    request_size_hint: Option<Hook<RequestSizeHintFn>>,
+    cache_status: Option<Hook<CacheStatusFn>>,
+    #[cfg(feature = "tracing")]
+    tracing_events: Option<tracing::Level>,
+    timeout_counter: bool,
+    sample_rate: f64,
+    dns_duration: bool,
+    omit_url_scheme: bool,
+    last_duration_gauge: bool,
+    error_type_fn: Option<Hook<ErrorTypeFn>>,
+    outcome: bool,
+    separate_connect_metrics: bool,
+    zero_head_response_body_size: bool,
+    on_complete: Option<Hook<OnCompleteFn>>,
+    detailed_error_type: bool,
+    omit_network_protocol_name: bool,
+    request_attempts: bool,
+    connection_reuse_label: bool,
+    operation_name: Option<Hook<OperationNameFn>>,
+    total_duration_counter: bool,
+    tls_version_label: bool,
+    seen_addresses: Option<SeenAddresses>,
+    started_counter: bool,
+    slow_request_threshold: Option<Duration>,
+    user_agent: bool,
+    user_agent_map: Option<Hook<UserAgentMapFn>>,
+    header_count: bool,
+    response_header_count: bool,
+    strict_otel_labels: bool,
+    time_to_first_byte: bool,
+    treat_upgrades_specially: bool,
+    record_duration_always: bool,
+    status_counter: bool,
 }
 
+/// The configuration used by [`MetricsMiddleware::new`], see
+/// [`MetricsMiddleware::set_global_defaults`].
+static GLOBAL_DEFAULTS: OnceLock<MetricsMiddlewareBuilder> = OnceLock::new();
+
 impl MetricsMiddleware {
-    /// Create a new [`MetricsMiddleware`] with default labels.
+    /// Create a new [`MetricsMiddleware`] with default labels, or with the configuration set with
+    /// [`MetricsMiddleware::set_global_defaults`].
     pub fn new() -> Self {
-        Self::new_inner(LabelNames::default())
+        match GLOBAL_DEFAULTS.get() {
+            Some(builder) => Self::new_inner(builder),
+            None => Self::new_inner(&MetricsMiddlewareBuilder::new()),
+        }
     }
 
-    fn new_inner(label_names: LabelNames) -> Self {
-        describe_histogram!(
-            HTTP_CLIENT_REQUEST_DURATION,
-            Unit::Seconds,
-            "Duration of HTTP client requests."
-        );
-        describe_histogram!(
-            HTTP_CLIENT_REQUEST_BODY_SIZE,
-            Unit::Bytes,
-            "Size of HTTP client request bodies."
+    /// Set the configuration used by every [`MetricsMiddleware::new`] (and
+    /// [`instrumented_client`]) call, ie. once at startup to share label and metric names between
+    /// all clients.
+    ///
+    /// The defaults can only be set once: later calls return
+    /// [`BuilderError::GlobalDefaultsAlreadySet`] and leave the defaults unchanged. Middlewares created before the defaults are set are not
+    /// affected, and [`MetricsMiddleware::builder`] always starts from the crate defaults.
+    ///
+    /// ```
+    /// use reqwest_metrics::MetricsMiddleware;
+    ///
+    /// let mut defaults = MetricsMiddleware::builder();
+    /// defaults.http_request_method_label("method");
+    /// MetricsMiddleware::set_global_defaults(defaults).unwrap();
+    ///
+    /// assert_eq!(MetricsMiddleware::new().http_request_method_label(), "method");
+    /// assert!(MetricsMiddleware::set_global_defaults(MetricsMiddleware::builder()).is_err());
+    /// ```
+    pub fn set_global_defaults(
+        builder: MetricsMiddlewareBuilder,
+    ) -> std::result::Result<(), BuilderError> {
+        GLOBAL_DEFAULTS
+            .set(builder)
+            .map_err(|_| BuilderError::GlobalDefaultsAlreadySet)
+    }
+
+    /// Create a new [`MetricsMiddleware`] with the label names read from the environment, see
+    /// [`LabelNamesConfig::from_env`] for the supported variables.
+    pub fn from_env() -> std::result::Result<Self, BuilderError> {
+        MetricsMiddlewareBuilder::new()
+            .with_label_names(LabelNamesConfig::from_env()?)
+            .try_build()
+    }
+
+    fn new_inner(builder: &MetricsMiddlewareBuilder) -> Self {
+        let metric_names = match &builder.metric_prefix {
+            Some(prefix) => builder.metric_names.prefixed(prefix),
+            None => builder.metric_names.clone(),
+        };
+        if !cfg!(feature = "disabled") {
+            Self::describe(builder, &metric_names);
+        }
+
+        Self {
+            inner: Arc::new(Inner {
+                label_names: builder.label_names.clone(),
+                metric_names,
+                peer_service: builder.peer_service.clone(),
+                constant_labels: builder.constant_labels.clone(),
+                server_address_header: builder.server_address_header.clone(),
+                server_port_header: builder.server_port_header.clone(),
+                duration_buckets: builder.duration_buckets.clone(),
+                request_body_size_buckets: builder.request_body_size_buckets.clone(),
+                response_body_size_buckets: builder.response_body_size_buckets.clone(),
+                network_protocol_name: builder.network_protocol_name.clone(),
+                error_status_excludes: builder.error_status_excludes.clone(),
+                client_name: builder.client_name.clone(),
+                duration_unit: builder.duration_unit,
+                body_size_unit: builder.body_size_unit,
+                active_requests: builder.active_requests,
+                request_body_size: builder.request_body_size,
+                response_body_size: builder.response_body_size,
+                status_class: builder.status_class,
+                url_template: builder.url_template.clone(),
+                dynamic_labels: builder.dynamic_labels.clone(),
+                measure_response_body: builder.measure_response_body,
+                skip_unknown_request_body_size: builder.skip_unknown_request_body_size,
+                resend_count: builder.resend_count.clone(),
+                connection_duration: builder.connection_duration,
+                recording_mode: builder.recording_mode,
+                gauge_summary_interval: builder.gauge_summary_interval,
+                response_content_type: builder.response_content_type,
+                trace_id: builder.trace_id,
+                skip_if: builder.skip_if.clone(),
+                treat_4xx_as_error: builder.treat_4xx_as_error,
+                network_transport: builder.network_transport,
+                record_request_size_eagerly: builder.record_request_size_eagerly,
+                omit_default_port: builder.omit_default_port,
+                redirect_count: builder.redirect_count,
+                clock: builder.clock.clone(),
+                sink: builder.sink.clone(),
+                collapse_unknown_methods: builder.collapse_unknown_methods,
+                labels_from_request: builder.labels_from_request.clone(),
+                url_full: builder.url_full,
+                url_redactor: builder.url_redactor.clone(),
+                request_size_hint: builder.request_size_hint.clone(),
+                cache_status: builder.cache_status.clone(),
+                #[cfg(feature = "tracing")]
+                tracing_events: builder.tracing_events,
+                timeout_counter: builder.timeout_counter,
+                sample_rate: builder.sample_rate,
+                dns_duration: builder.dns_duration,
+                omit_url_scheme: builder.omit_url_scheme,
+                last_duration_gauge: builder.last_duration_gauge,
+                error_type_fn: builder.error_type_fn.clone(),
+                outcome: builder.outcome,
+                separate_connect_metrics: builder.separate_connect_metrics,
+                zero_head_response_body_size: builder.zero_head_response_body_size,
+                on_complete: builder.on_complete.clone(),
+                detailed_error_type: builder.detailed_error_type,
+                omit_network_protocol_name: builder.omit_network_protocol_name,
+                request_attempts: builder.request_attempts,
+                connection_reuse_label: builder.connection_reuse_label,
+                operation_name: builder.operation_name.clone(),
+                total_duration_counter: builder.total_duration_counter,
+                tls_version_label: builder.tls_version_label,
+                seen_addresses: builder.max_address_cardinality.map(SeenAddresses::new),
+                started_counter: builder.started_counter,
+                slow_request_threshold: builder.slow_request_threshold,
+                user_agent: builder.user_agent,
+                user_agent_map: builder.user_agent_map.clone(),
+                header_count: builder.header_count,
+                response_header_count: builder.response_header_count,
+                strict_otel_labels: builder.strict_otel_labels,
+                time_to_first_byte: builder.time_to_first_byte,
+                treat_upgrades_specially: builder.treat_upgrades_specially,
+                record_duration_always: builder.record_duration_always,
+                status_counter: builder.status_counter,
+            }),
+        }
+    }
+
+    /// Describes the metrics enabled by `builder`, named `metric_names`.
+    fn describe(builder: &MetricsMiddlewareBuilder, metric_names: &MetricNames) {
+        builder.recording_mode.describe(
+            &metric_names.request_duration,
+            builder.duration_unit.unit(),
+            builder
+                .request_duration_description
+                .as_deref()
+                .unwrap_or("Duration of HTTP client requests."),
         );
-        describe_histogram!(
-            HTTP_CLIENT_RESPONSE_BODY_SIZE,
-            Unit::Bytes,
-            "Size of HTTP client response bodies."
+        if builder.slow_request_threshold.is_some() {
+            describe_once(
+                MetricKind::Counter,
+                &metric_names.slow_request_count,
+                Unit::Count,
+                "Number of HTTP client requests slower than the configured threshold.",
+            );
+        }
+        if builder.status_counter {
+            describe_once(
+                MetricKind::Counter,
+                &metric_names.response_count,
+                Unit::Count,
+                "Number of HTTP client responses by status code.",
+            );
+        }
+        if builder.started_counter {
+            describe_once(
+                MetricKind::Counter,
+                &metric_names.request_started,
+                Unit::Count,
+                "Number of HTTP client requests that were started.",
+            );
+        }
+        if builder.total_duration_counter {
+            describe_once(
+                MetricKind::Counter,
+                &metric_names.request_duration_total,
+                Unit::Microseconds,
+                "Total duration of HTTP client requests.",
+            );
+        }
+        if builder.separate_connect_metrics {
+            builder.recording_mode.describe(
+                &metric_names.connect_duration,
+                builder.duration_unit.unit(),
+                "Duration of HTTP client CONNECT requests.",
+            );
+        }
+        if builder.treat_upgrades_specially {
+            builder.recording_mode.describe(
+                &metric_names.upgrade_duration,
+                builder.duration_unit.unit(),
+                "Duration of HTTP client requests upgraded to another protocol.",
+            );
+        }
+        if builder.request_body_size {
+            builder.recording_mode.describe(
+                &metric_names.request_body_size,
+                builder.body_size_unit.unit(),
+                builder
+                    .request_body_size_description
+                    .as_deref()
+                    .unwrap_or("Size of HTTP client request bodies."),
+            );
+        }
+        if builder.response_body_size {
+            builder.recording_mode.describe(
+                &metric_names.response_body_size,
+                builder.body_size_unit.unit(),
+                builder
+                    .response_body_size_description
+                    .as_deref()
+                    .unwrap_or("Size of HTTP client response bodies."),
+            );
+        }
+        describe_once(
+            MetricKind::Counter,
+            &metric_names.request_count,
+            Unit::Count,
+            "Number of HTTP client requests.",
         );
-        Self { label_names }
+        if builder.connection_duration {
+            builder.recording_mode.describe(
+                &metric_names.connection_duration,
+                builder.duration_unit.unit(),
+                "Duration of establishing outbound HTTP connections.",
+            );
+        }
+        if builder.dns_duration {
+            builder.recording_mode.describe(
+                &metric_names.dns_duration,
+                builder.duration_unit.unit(),
+                "Duration of resolving the addresses of HTTP servers.",
+            );
+        }
+        if builder.time_to_first_byte {
+            builder.recording_mode.describe(
+                &metric_names.time_to_first_byte,
+                builder.duration_unit.unit(),
+                "Time until the response headers of HTTP client requests were received.",
+            );
+        }
+        if builder.header_count {
+            builder.recording_mode.describe(
+                &metric_names.request_header_count,
+                Unit::Count,
+                "Number of headers of HTTP client requests.",
+            );
+        }
+        if builder.response_header_count {
+            builder.recording_mode.describe(
+                &metric_names.response_header_count,
+                Unit::Count,
+                "Number of headers of HTTP client responses.",
+            );
+        }
+        if builder.request_attempts {
+            builder.recording_mode.describe(
+                &metric_names.request_attempts,
+                Unit::Count,
+                "Number of attempts made to send HTTP client requests.",
+            );
+            builder.recording_mode.describe(
+                &metric_names.attempt_duration,
+                builder.duration_unit.unit(),
+                "Duration of the individual attempts of HTTP client requests.",
+            );
+        }
+        if builder.redirect_count {
+            describe_once(
+                MetricKind::Counter,
+                &metric_names.redirect_count,
+                Unit::Count,
+                "Number of HTTP client requests that were redirected.",
+            );
+        }
+        if builder.timeout_counter {
+            describe_once(
+                MetricKind::Counter,
+                &metric_names.request_timeouts,
+                Unit::Count,
+                "Number of HTTP client requests that timed out.",
+            );
+        }
+        if builder.last_duration_gauge {
+            describe_once(
+                MetricKind::Gauge,
+                &metric_names.request_last_duration,
+                builder.duration_unit.unit(),
+                "Duration of the last HTTP client request to a server.",
+            );
+        }
+        if builder.active_requests {
+            describe_once(
+                MetricKind::Gauge,
+                &metric_names.active_requests,
+                Unit::Count,
+                "Number of active HTTP client requests.",
+            );
+        }
     }
 
     /// Create a new [`MetricsMiddlewareBuilder`] to create a customized [`MetricsMiddleware`]
     pub fn builder() -> MetricsMiddlewareBuilder {
         MetricsMiddlewareBuilder::new()
     }
+
+    /// The name of the `http.client.request.duration` metric.
+    pub fn request_duration_metric(&self) -> &str {
+        &self.inner.metric_names.request_duration
+    }
+
+    /// The name of the `http.client.request.body.size` metric.
+    pub fn request_body_size_metric(&self) -> &str {
+        &self.inner.metric_names.request_body_size
+    }
+
+    /// The name of the `http.client.response.body.size` metric.
+    pub fn response_body_size_metric(&self) -> &str {
+        &self.inner.metric_names.response_body_size
+    }
+
+    /// The buckets configured with [`MetricsMiddlewareBuilder::duration_buckets`], if any.
+    pub fn duration_buckets(&self) -> Option<&[f64]> {
+        self.inner.duration_buckets.as_deref()
+    }
+
+    /// The buckets configured with [`MetricsMiddlewareBuilder::request_body_size_buckets`], if
+    /// any.
+    pub fn request_body_size_buckets(&self) -> Option<&[f64]> {
+        self.inner.request_body_size_buckets.as_deref()
+    }
+
+    /// The buckets configured with [`MetricsMiddlewareBuilder::response_body_size_buckets`], if
+    /// any.
+    pub fn response_body_size_buckets(&self) -> Option<&[f64]> {
+        self.inner.response_body_size_buckets.as_deref()
+    }
+
+    /// Records the metrics of a request sent without the middleware, ie. with a plain
+    /// [`reqwest::Client`](reqwest_middleware::reqwest::Client), timed by the caller.
+    ///
+    /// This records the same metrics as the middleware, except for the
+    /// `http.client.active_requests` gauge. The response body size is always taken from the
+    /// `content-length` header.
+    ///
+    /// ```no_run
+    /// # async fn run() {
+    /// use std::time::Instant;
+    ///
+    /// use reqwest_metrics::MetricsMiddleware;
+    ///
+    /// let metrics = MetricsMiddleware::new();
+    /// let client = reqwest::Client::new();
+    ///
+    /// let req = client.get("https://www.rust-lang.org").build().unwrap();
+    /// let start = Instant::now();
+    /// let res = client
+    ///     .execute(req.try_clone().unwrap())
+    ///     .await
+    ///     .map_err(Into::into);
+    /// metrics.record(&req, &res, start.elapsed());
+    /// # }
+    /// ```
+    pub fn record(&self, req: &Request, res: &Result<Response>, duration: Duration) {
+        if cfg!(feature = "disabled") || self.skip(req) {
+            return;
+        }
+        let extensions = Extensions::new();
+        let outcome = self.outcome(
+            res,
+            &extensions,
+            self.inner.redirect_count.then(|| req.url()),
+        );
+        if !self.sampled() {
+            if self.inner.record_duration_always {
+                let labels = self.completed_labels(self.request_labels(req, true), &outcome);
+                self.record_duration(&labels, duration);
+            }
+            return;
+        }
+
+        let labels = self.record_completed(
+            self.request_labels(req, true),
+            self.request_size(req),
+            req.headers().len(),
+            &outcome,
+            duration,
+        );
+        self.record_known_response_body_size(
+            &labels,
+            req.method() == Method::HEAD,
+            res.as_ref().ok().and_then(response_body_size),
+        );
+        self.record_counters(&labels, outcome.failed, outcome.redirected);
+
+        if let Some(on_complete) = &self.inner.on_complete {
+            (on_complete.0)(&owned_keys(&labels), duration, res);
+        }
+    }
+
+    /// Computes the labels the middleware records for `req` and its response, ie. to reuse them
+    /// in custom metrics or logs.
+    ///
+    /// Labels read from the request [`Extensions`] are not included, since they are not available
+    /// outside of the middleware. The `server.address` of `req` does not count towards
+    /// [`MetricsMiddlewareBuilder::max_address_cardinality`], since no metrics are recorded.
+    ///
+    /// ```no_run
+    /// # async fn run() {
+    /// use reqwest_metrics::MetricsMiddleware;
+    ///
+    /// let metrics = MetricsMiddleware::new();
+    /// let client = reqwest::Client::new();
+    ///
+    /// let req = client.get("https://www.rust-lang.org").build().unwrap();
+    /// let res = client
+    ///     .execute(req.try_clone().unwrap())
+    ///     .await
+    ///     .map_err(Into::into);
+    /// let labels = metrics.compute_labels(&req, &res);
+    /// # }
+    /// ```
+    pub fn compute_labels(
+        &self,
+        req: &Request,
+        res: &Result<Response>,
+    ) -> Vec<(String, Cow<'static, str>)> {
+        let extensions = Extensions::new();
+        owned_keys(&self.completed_labels(
+            self.request_labels(req, false),
+            &self.outcome(res, &extensions, None),
+        ))
+    }
+}
+
+/// The unit used when recording the `http.client.request.duration` histogram.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DurationUnit {
+    /// Record durations in seconds, as recommended by Open Telemetry.
+    #[default]
+    Seconds,
+    /// Record durations in milliseconds.
+    Milliseconds,
+}
+
+impl DurationUnit {
+    fn unit(self) -> Unit {
+        match self {
+            DurationUnit::Seconds => Unit::Seconds,
+            DurationUnit::Milliseconds => Unit::Milliseconds,
+        }
+    }
+
+    fn value(self, duration: Duration) -> f64 {
+        match self {
+            DurationUnit::Seconds => duration.as_secs_f64(),
+            DurationUnit::Milliseconds => duration.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+/// The unit used when recording the `http.client.request.body.size` and
+/// `http.client.response.body.size` histograms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BodySizeUnit {
+    /// Record sizes in bytes, as recommended by Open Telemetry.
+    #[default]
+    Bytes,
+    /// Record sizes in kibibytes (1024 bytes).
+    Kibibytes,
+    /// Record sizes in mebibytes (1024 kibibytes).
+    Mebibytes,
+    /// Record sizes in gibibytes (1024 mebibytes).
+    Gibibytes,
+}
+
+impl BodySizeUnit {
+    fn unit(self) -> Unit {
+        match self {
+            BodySizeUnit::Bytes => Unit::Bytes,
+            BodySizeUnit::Kibibytes => Unit::Kibibytes,
+            BodySizeUnit::Mebibytes => Unit::Mebibytes,
+            BodySizeUnit::Gibibytes => Unit::Gibibytes,
+        }
+    }
+
+    fn value(self, size: u64) -> f64 {
+        match self {
+            BodySizeUnit::Bytes => size as f64,
+            BodySizeUnit::Kibibytes => size as f64 / 1024.0,
+            BodySizeUnit::Mebibytes => size as f64 / (1024.0 * 1024.0),
+            BodySizeUnit::Gibibytes => size as f64 / (1024.0 * 1024.0 * 1024.0),
+        }
+    }
+}
+
+/// A source of time used to measure the duration of requests.
+///
+/// The default [`SystemClock`] can be replaced with [`MetricsMiddlewareBuilder::with_clock`],
+/// ie. to record known durations in tests.
+pub trait Clock: Send + Sync + 'static {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+}
+
+/// A [`Clock`] backed by [`Instant::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// The destination of the metrics recorded by a [`MetricsMiddleware`].
+///
+/// The default [`GlobalSink`] can be replaced with [`MetricsMiddlewareBuilder::with_sink`], ie. to
+/// capture metrics in tests without installing a global recorder. Metric descriptions are always
+/// sent to the current recorder.
+pub trait MetricsSink: Send + Sync + 'static {
+    /// Returns the counter identified by `key`.
+    fn counter(&self, key: &Key) -> Counter;
+
+    /// Returns the gauge identified by `key`.
+    fn gauge(&self, key: &Key) -> Gauge;
+
+    /// Returns the histogram identified by `key`.
+    fn histogram(&self, key: &Key) -> Histogram;
+}
+
+/// A [`MetricsSink`] forwarding to the current `metrics` recorder, like the `metrics` macros.
+///
+/// Local recorders (ie. set with `metrics::with_local_recorder`) take precedence over the global
+/// recorder. They are thread-local, so requests are only recorded to them when the future is
+/// polled on that thread, ie. with a current-thread runtime.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlobalSink;
+
+static METADATA: Metadata<'static> =
+    Metadata::new(module_path!(), Level::INFO, Some(module_path!()));
+
+impl MetricsSink for GlobalSink {
+    fn counter(&self, key: &Key) -> Counter {
+        metrics::with_recorder(|recorder| recorder.register_counter(key, &METADATA))
+    }
+
+    fn gauge(&self, key: &Key) -> Gauge {
+        metrics::with_recorder(|recorder| recorder.register_gauge(key, &METADATA))
+    }
+
+    fn histogram(&self, key: &Key) -> Histogram {
+        metrics::with_recorder(|recorder| recorder.register_histogram(key, &METADATA))
+    }
+}
+
+/// The time spent establishing the connection used for a request, recorded to the
+/// `http.client.connection.duration` histogram when enabled with
+/// [`MetricsMiddlewareBuilder::enable_connection_duration`].
+///
+/// reqwest does not expose connection timings itself, so this is read from the response
+/// extensions (ie. inserted by a custom connector through `hyper_util`'s `Connected::extra`)
+/// or from the request [`Extensions`] (ie. inserted by another middleware).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionDuration(pub Duration);
+
+/// The time spent resolving the address of the server for a request, recorded to the
+/// `http.client.dns.duration` histogram when enabled with
+/// [`MetricsMiddlewareBuilder::enable_dns_duration`].
+///
+/// Like [`ConnectionDuration`], this is read from the response extensions (ie. inserted by a
+/// custom connector) or from the request [`Extensions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DnsDuration(pub Duration);
+
+/// Whether the connection used for a request was reused from the pool, recorded as the
+/// `connection.reused` label when enabled with
+/// [`MetricsMiddlewareBuilder::enable_connection_reuse_label`].
+///
+/// Like [`ConnectionDuration`], this is read from the response extensions (ie. inserted by a
+/// custom connector) or from the request [`Extensions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionReused(pub bool);
+
+/// The TLS version negotiated for the connection used by a request (ie. `1.3`), recorded as the
+/// `tls.protocol.version` label when enabled with
+/// [`MetricsMiddlewareBuilder::enable_tls_version_label`].
+///
+/// reqwest does not expose the TLS version (its `tls_info` option only exposes the peer
+/// certificate), so like [`ConnectionDuration`] this is read from the response extensions (ie.
+/// inserted by a custom connector) or from the request [`Extensions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsVersion(pub &'static str);
+
+type UrlTemplateFn = dyn Fn(&Url) -> Option<String> + Send + Sync;
+type DynamicLabelsFn = dyn Fn(&Extensions) -> Vec<(String, String)> + Send + Sync;
+type ResendCountFn = dyn Fn(&Extensions) -> Option<u32> + Send + Sync;
+type SkipFn = dyn Fn(&Request) -> bool + Send + Sync;
+type RequestLabelsFn = dyn Fn(&Request) -> Vec<(String, String)> + Send + Sync;
+type UrlRedactorFn = dyn Fn(&Url) -> String + Send + Sync;
+type RequestSizeHintFn = dyn Fn(&Request) -> Option<u64> + Send + Sync;
+type CacheStatusFn = dyn Fn(&Extensions) -> Option<bool> + Send + Sync;
+type OperationNameFn = dyn Fn(&Extensions) -> Option<String> + Send + Sync;
+type UserAgentMapFn = dyn Fn(&str) -> Cow<'_, str> + Send + Sync;
+type ErrorTypeFn = dyn Fn(&Result<Response>) -> Option<Cow<'static, str>> + Send + Sync;
+type OnCompleteFn =
+    dyn Fn(&[(String, Cow<'static, str>)], Duration, &Result<Response>) + Send + Sync;
+
+/// The attempts made to send a request, recorded to the `http.client.request.attempts` and
+/// `http.client.request.attempt.duration` histograms when enabled with
+/// [`MetricsMiddlewareBuilder::enable_request_attempts`].
+///
+/// Retry middlewares placed after [`MetricsMiddleware`] can insert this into the request
+/// [`Extensions`], which are read once the request completed. Retry layers wrapped by
+/// `MetricsLayer` insert it into the response extensions instead. Nothing is recorded when it is
+/// absent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestAttempts {
+    /// The number of attempts made, including the first one.
+    pub count: u32,
+    /// The duration of each attempt, if the retry middleware tracks them.
+    pub durations: Vec<Duration>,
+}
+
+/// The number of times a request has been resent, recorded as the `http.request.resend_count`
+/// label when present in the request [`Extensions`].
+///
+/// Retry middlewares placed before [`MetricsMiddleware`] can insert this before each attempt.
+/// Use [`MetricsMiddlewareBuilder::resend_count_fn`] to read the count from a different type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResendCount(pub u32);
+
+/// The class of a request (ie. `interactive` or `batch`), recorded as the `request.class` label
+/// when present in the request [`Extensions`].
+///
+/// ```
+/// use reqwest_metrics::{MetricsMiddleware, RequestClass};
+/// use reqwest_middleware::ClientBuilder;
+///
+/// let client = ClientBuilder::new(reqwest::Client::new())
+///     .with(MetricsMiddleware::new())
+///     .build();
+/// let request = client
+///     .get("https://www.rust-lang.org")
+///     .with_extension(RequestClass("batch"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestClass(pub &'static str);
+
+/// Wrapper around user provided functions so the middleware and builder can stay `Debug` and
+/// cheaply `Clone`.
+struct Hook<F: ?Sized>(Arc<F>);
+
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
+
+/// The `server.address` values recorded by a [`MetricsMiddleware`] (and its clones), to bound
+/// their number with [`MetricsMiddlewareBuilder::max_address_cardinality`].
+#[derive(Debug)]
+struct SeenAddresses {
+    max: usize,
+    addresses: Mutex<HashSet<String>>,
+}
+
+impl SeenAddresses {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            addresses: Mutex::default(),
+        }
+    }
+
+    /// Returns `address` if it was already seen or there is room for it, and `_OTHER` otherwise.
+    ///
+    /// A new address only takes up room when `record` is set.
+    fn bound(&self, address: String, record: bool) -> String {
+        let mut addresses = self
+            .addresses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if addresses.contains(&address) {
+            address
+        } else if addresses.len() < self.max {
+            if record {
+                addresses.insert(address.clone());
+            }
+            address
+        } else {
+            "_OTHER".to_string()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MetricNames {
+    request_duration: String,
+    request_body_size: String,
+    response_body_size: String,
+    active_requests: String,
+    connection_duration: String,
+    dns_duration: String,
+    request_count: String,
+    redirect_count: String,
+    request_timeouts: String,
+    request_last_duration: String,
+    connect_duration: String,
+    request_attempts: String,
+    attempt_duration: String,
+    request_duration_total: String,
+    request_started: String,
+    slow_request_count: String,
+    request_header_count: String,
+    response_header_count: String,
+    time_to_first_byte: String,
+    upgrade_duration: String,
+    response_count: String,
+}
+
+impl Default for MetricNames {
+    fn default() -> Self {
+        Self {
+            request_duration: HTTP_CLIENT_REQUEST_DURATION.to_string(),
+            request_body_size: HTTP_CLIENT_REQUEST_BODY_SIZE.to_string(),
+            response_body_size: HTTP_CLIENT_RESPONSE_BODY_SIZE.to_string(),
+            active_requests: HTTP_CLIENT_ACTIVE_REQUESTS.to_string(),
+            connection_duration: HTTP_CLIENT_CONNECTION_DURATION.to_string(),
+            dns_duration: HTTP_CLIENT_DNS_DURATION.to_string(),
+            request_count: HTTP_CLIENT_REQUEST_COUNT.to_string(),
+            redirect_count: HTTP_CLIENT_REDIRECT_COUNT.to_string(),
+            request_timeouts: HTTP_CLIENT_REQUEST_TIMEOUTS.to_string(),
+            request_last_duration: HTTP_CLIENT_REQUEST_LAST_DURATION.to_string(),
+            connect_duration: HTTP_CLIENT_CONNECT_DURATION.to_string(),
+            request_attempts: HTTP_CLIENT_REQUEST_ATTEMPTS.to_string(),
+            attempt_duration: HTTP_CLIENT_REQUEST_ATTEMPT_DURATION.to_string(),
+            request_duration_total: HTTP_CLIENT_REQUEST_DURATION_TOTAL.to_string(),
+            request_started: HTTP_CLIENT_REQUEST_STARTED.to_string(),
+            slow_request_count: HTTP_CLIENT_REQUEST_SLOW_COUNT.to_string(),
+            request_header_count: HTTP_CLIENT_REQUEST_HEADER_COUNT.to_string(),
+            response_header_count: HTTP_CLIENT_RESPONSE_HEADER_COUNT.to_string(),
+            time_to_first_byte: HTTP_CLIENT_RESPONSE_TIME_TO_FIRST_BYTE.to_string(),
+            upgrade_duration: HTTP_CLIENT_UPGRADE_DURATION.to_string(),
+            response_count: HTTP_CLIENT_RESPONSE_COUNT.to_string(),
+        }
+    }
+}
+
+impl MetricNames {
+    /// Returns the metric names with `prefix` prepended.
+    fn prefixed(&self, prefix: &str) -> Self {
+        let Self {
+            request_duration,
+            request_body_size,
+            response_body_size,
+            active_requests,
+            connection_duration,
+            dns_duration,
+            request_count,
+            redirect_count,
+            request_timeouts,
+            request_last_duration,
+            connect_duration,
+            request_attempts,
+            attempt_duration,
+            request_duration_total,
+            request_started,
+            slow_request_count,
+            request_header_count,
+            response_header_count,
+            time_to_first_byte,
+            upgrade_duration,
+            response_count,
+        } = self;
+        Self {
+            request_duration: format!("{prefix}{request_duration}"),
+            request_body_size: format!("{prefix}{request_body_size}"),
+            response_body_size: format!("{prefix}{response_body_size}"),
+            active_requests: format!("{prefix}{active_requests}"),
+            connection_duration: format!("{prefix}{connection_duration}"),
+            dns_duration: format!("{prefix}{dns_duration}"),
+            request_count: format!("{prefix}{request_count}"),
+            redirect_count: format!("{prefix}{redirect_count}"),
+            request_timeouts: format!("{prefix}{request_timeouts}"),
+            request_last_duration: format!("{prefix}{request_last_duration}"),
+            connect_duration: format!("{prefix}{connect_duration}"),
+            request_attempts: format!("{prefix}{request_attempts}"),
+            attempt_duration: format!("{prefix}{attempt_duration}"),
+            request_duration_total: format!("{prefix}{request_duration_total}"),
+            request_started: format!("{prefix}{request_started}"),
+            slow_request_count: format!("{prefix}{slow_request_count}"),
+            request_header_count: format!("{prefix}{request_header_count}"),
+            response_header_count: format!("{prefix}{response_header_count}"),
+            time_to_first_byte: format!("{prefix}{time_to_first_byte}"),
+            upgrade_duration: format!("{prefix}{upgrade_duration}"),
+            response_count: format!("{prefix}{response_count}"),
+        }
+    }
+}
+
+/// Label names to override with [`MetricsMiddlewareBuilder::with_label_names`].
+///
+/// Labels left as `None` keep their current name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelNamesConfig {
+    /// The name of the `http.request.method` label.
+    pub http_request_method: Option<String>,
+    /// The name of the `server.address` label.
+    pub server_address: Option<String>,
+    /// The name of the `server.port` label.
+    pub server_port: Option<String>,
+    /// The name of the `error.type` label.
+    pub error_type: Option<String>,
+    /// The name of the `http.response.status_code` label.
+    pub http_response_status: Option<String>,
+    /// The name of the `http.response.status_class` label.
+    pub http_response_status_class: Option<String>,
+    /// The name of the `network.protocol.name` label.
+    pub network_protocol_name: Option<String>,
+    /// The name of the `network.protocol.version` label.
+    pub network_protocol_version: Option<String>,
+    /// The name of the `url.scheme` label.
+    pub url_scheme: Option<String>,
+    /// The name of the `http.route` label.
+    pub http_route: Option<String>,
+    /// The name of the `peer.service` label.
+    pub peer_service: Option<String>,
+    /// The name of the `http.request.resend_count` label.
+    pub http_request_resend_count: Option<String>,
+    /// The name of the `http.response.header.content-type` label.
+    pub http_response_content_type: Option<String>,
+    /// The name of the `trace_id` label.
+    pub trace_id: Option<String>,
+    /// The name of the `network.transport` label.
+    pub network_transport: Option<String>,
+    /// The name of the `url.full` label.
+    pub url_full: Option<String>,
+    /// The name of the `cache.hit` label.
+    pub cache_hit: Option<String>,
+    /// The name of the `outcome` label.
+    pub outcome: Option<String>,
+    /// The name of the `connection.reused` label.
+    pub connection_reused: Option<String>,
+    /// The name of the `operation` label.
+    pub operation: Option<String>,
+    /// The name of the `tls.protocol.version` label.
+    pub tls_protocol_version: Option<String>,
+    /// The name of the `http.client.name` label.
+    pub http_client_name: Option<String>,
+    /// The name of the `user_agent.original` label.
+    pub user_agent_original: Option<String>,
+    /// The name of the `request.class` label.
+    pub request_class: Option<String>,
+}
+
+impl LabelNamesConfig {
+    /// Read the label names from the environment, leaving the labels without a variable as `None`.
+    ///
+    /// | Variable | Label |
+    /// |---|---|
+    /// | `REQWEST_METRICS_METHOD_LABEL` | `http.request.method` |
+    /// | `REQWEST_METRICS_SERVER_ADDRESS_LABEL` | `server.address` |
+    /// | `REQWEST_METRICS_SERVER_PORT_LABEL` | `server.port` |
+    /// | `REQWEST_METRICS_ERROR_TYPE_LABEL` | `error.type` |
+    /// | `REQWEST_METRICS_STATUS_LABEL` | `http.response.status_code` |
+    /// | `REQWEST_METRICS_STATUS_CLASS_LABEL` | `http.response.status_class` |
+    /// | `REQWEST_METRICS_NETWORK_PROTOCOL_NAME_LABEL` | `network.protocol.name` |
+    /// | `REQWEST_METRICS_NETWORK_PROTOCOL_VERSION_LABEL` | `network.protocol.version` |
+    /// | `REQWEST_METRICS_URL_SCHEME_LABEL` | `url.scheme` |
+    /// | `REQWEST_METRICS_ROUTE_LABEL` | `http.route` |
+    /// | `REQWEST_METRICS_PEER_SERVICE_LABEL` | `peer.service` |
+    /// | `REQWEST_METRICS_RESEND_COUNT_LABEL` | `http.request.resend_count` |
+    /// | `REQWEST_METRICS_CONTENT_TYPE_LABEL` | `http.response.header.content-type` |
+    /// | `REQWEST_METRICS_TRACE_ID_LABEL` | `trace_id` |
+    /// | `REQWEST_METRICS_NETWORK_TRANSPORT_LABEL` | `network.transport` |
+    /// | `REQWEST_METRICS_URL_FULL_LABEL` | `url.full` |
+    /// | `REQWEST_METRICS_CACHE_HIT_LABEL` | `cache.hit` |
+    /// | `REQWEST_METRICS_OUTCOME_LABEL` | `outcome` |
+    /// | `REQWEST_METRICS_CONNECTION_REUSED_LABEL` | `connection.reused` |
+    /// | `REQWEST_METRICS_OPERATION_LABEL` | `operation` |
+    /// | `REQWEST_METRICS_TLS_PROTOCOL_VERSION_LABEL` | `tls.protocol.version` |
+    /// | `REQWEST_METRICS_CLIENT_NAME_LABEL` | `http.client.name` |
+    /// | `REQWEST_METRICS_USER_AGENT_LABEL` | `user_agent.original` |
+    /// | `REQWEST_METRICS_REQUEST_CLASS_LABEL` | `request.class` |
+    ///
+    /// Returns [`BuilderError::InvalidEnvVar`] if a variable is empty or not valid unicode.
+    pub fn from_env() -> std::result::Result<Self, BuilderError> {
+        fn var(name: &'static str) -> std::result::Result<Option<String>, BuilderError> {
+            match std::env::var(name) {
+                Ok(value) if !value.trim().is_empty() => Ok(Some(value)),
+                Err(std::env::VarError::NotPresent) => Ok(None),
+                _ => Err(BuilderError::InvalidEnvVar { name }),
+            }
+        }
+
+        Ok(Self {
+            http_request_method: var("REQWEST_METRICS_METHOD_LABEL")?,
+            server_address: var("REQWEST_METRICS_SERVER_ADDRESS_LABEL")?,
+            server_port: var("REQWEST_METRICS_SERVER_PORT_LABEL")?,
+            error_type: var("REQWEST_METRICS_ERROR_TYPE_LABEL")?,
+            http_response_status: var("REQWEST_METRICS_STATUS_LABEL")?,
+            http_response_status_class: var("REQWEST_METRICS_STATUS_CLASS_LABEL")?,
+            network_protocol_name: var("REQWEST_METRICS_NETWORK_PROTOCOL_NAME_LABEL")?,
+            network_protocol_version: var("REQWEST_METRICS_NETWORK_PROTOCOL_VERSION_LABEL")?,
+            url_scheme: var("REQWEST_METRICS_URL_SCHEME_LABEL")?,
+            http_route: var("REQWEST_METRICS_ROUTE_LABEL")?,
+            peer_service: var("REQWEST_METRICS_PEER_SERVICE_LABEL")?,
+            http_request_resend_count: var("REQWEST_METRICS_RESEND_COUNT_LABEL")?,
+            http_response_content_type: var("REQWEST_METRICS_CONTENT_TYPE_LABEL")?,
+            trace_id: var("REQWEST_METRICS_TRACE_ID_LABEL")?,
+            network_transport: var("REQWEST_METRICS_NETWORK_TRANSPORT_LABEL")?,
+            url_full: var("REQWEST_METRICS_URL_FULL_LABEL")?,
+            cache_hit: var("REQWEST_METRICS_CACHE_HIT_LABEL")?,
+            outcome: var("REQWEST_METRICS_OUTCOME_LABEL")?,
+            connection_reused: var("REQWEST_METRICS_CONNECTION_REUSED_LABEL")?,
+            operation: var("REQWEST_METRICS_OPERATION_LABEL")?,
+            tls_protocol_version: var("REQWEST_METRICS_TLS_PROTOCOL_VERSION_LABEL")?,
+            http_client_name: var("REQWEST_METRICS_CLIENT_NAME_LABEL")?,
+            user_agent_original: var("REQWEST_METRICS_USER_AGENT_LABEL")?,
+            request_class: var("REQWEST_METRICS_REQUEST_CLASS_LABEL")?,
+        })
+    }
 }
 
+/// The labels OTel defines for `http.client.request.duration` and the body size histograms.
+const OTEL_REQUEST_LABELS: [&str; 8] = [
+    HTTP_REQUEST_METHOD,
+    SERVER_ADDRESS,
+    SERVER_PORT,
+    ERROR_TYPE,
+    HTTP_RESPONSE_STATUS_CODE,
+    NETWORK_PROTOCOL_NAME,
+    NETWORK_PROTOCOL_VERSION,
+    URL_SCHEME,
+];
+
+/// The labels OTel defines for `http.client.active_requests` that are known while a request is in
+/// flight.
+const OTEL_ACTIVE_REQUESTS_LABELS: [&str; 3] = [HTTP_REQUEST_METHOD, SERVER_ADDRESS, URL_SCHEME];
+
+/// The labels OTel defines for `http.client.connection.duration`.
+const OTEL_CONNECTION_DURATION_LABELS: [&str; 4] = [
+    SERVER_ADDRESS,
+    SERVER_PORT,
+    NETWORK_PROTOCOL_VERSION,
+    URL_SCHEME,
+];
+
 #[derive(Debug, Clone)]
 struct LabelNames {
-    http_request_method: String,
-    server_address: String,
-    server_port: String,
-    error_type: String,
-    http_response_status: String,
-    network_protocol_name: String,
-    network_protocol_version: String,
-    url_scheme: String,
+    http_request_method: Cow<'static, str>,
+    server_address: Cow<'static, str>,
+    server_port: Cow<'static, str>,
+    error_type: Cow<'static, str>,
+    http_response_status: Cow<'static, str>,
+    http_response_status_class: Cow<'static, str>,
+    network_protocol_name: Cow<'static, str>,
+    network_protocol_version: Cow<'static, str>,
+    url_scheme: Cow<'static, str>,
+    http_route: Cow<'static, str>,
+    peer_service: Cow<'static, str>,
+    http_request_resend_count: Cow<'static, str>,
+    http_response_content_type: Cow<'static, str>,
+    trace_id: Cow<'static, str>,
+    network_transport: Cow<'static, str>,
+    url_full: Cow<'static, str>,
+    cache_hit: Cow<'static, str>,
+    outcome: Cow<'static, str>,
+    connection_reused: Cow<'static, str>,
+    operation: Cow<'static, str>,
+    tls_protocol_version: Cow<'static, str>,
+    http_client_name: Cow<'static, str>,
+    user_agent_original: Cow<'static, str>,
+    request_class: Cow<'static, str>,
 }
 
 impl Default for LabelNames {
     fn default() -> Self {
         Self {
-            http_request_method: HTTP_REQUEST_METHOD.to_string(),
-            server_address: SERVER_ADDRESS.to_string(),
-            server_port: SERVER_PORT.to_string(),
-            error_type: ERROR_TYPE.to_string(),
-            http_response_status: HTTP_RESPONSE_STATUS_CODE.to_string(),
-            network_protocol_name: NETWORK_PROTOCOL_NAME.to_string(),
-            network_protocol_version: NETWORK_PROTOCOL_VERSION.to_string(),
-            url_scheme: URL_SCHEME.to_string(),
+            http_request_method: Cow::Borrowed(HTTP_REQUEST_METHOD),
+            server_address: Cow::Borrowed(SERVER_ADDRESS),
+            server_port: Cow::Borrowed(SERVER_PORT),
+            error_type: Cow::Borrowed(ERROR_TYPE),
+            http_response_status: Cow::Borrowed(HTTP_RESPONSE_STATUS_CODE),
+            http_response_status_class: Cow::Borrowed(HTTP_RESPONSE_STATUS_CLASS),
+            network_protocol_name: Cow::Borrowed(NETWORK_PROTOCOL_NAME),
+            network_protocol_version: Cow::Borrowed(NETWORK_PROTOCOL_VERSION),
+            url_scheme: Cow::Borrowed(URL_SCHEME),
+            http_route: Cow::Borrowed(HTTP_ROUTE),
+            peer_service: Cow::Borrowed(PEER_SERVICE),
+            http_request_resend_count: Cow::Borrowed(HTTP_REQUEST_RESEND_COUNT),
+            http_response_content_type: Cow::Borrowed(HTTP_RESPONSE_HEADER_CONTENT_TYPE),
+            trace_id: Cow::Borrowed(TRACE_ID),
+            network_transport: Cow::Borrowed(NETWORK_TRANSPORT),
+            url_full: Cow::Borrowed(URL_FULL),
+            cache_hit: Cow::Borrowed(CACHE_HIT),
+            outcome: Cow::Borrowed(OUTCOME),
+            connection_reused: Cow::Borrowed(CONNECTION_REUSED),
+            operation: Cow::Borrowed(OPERATION),
+            tls_protocol_version: Cow::Borrowed(TLS_PROTOCOL_VERSION),
+            http_client_name: Cow::Borrowed(HTTP_CLIENT_NAME),
+            user_agent_original: Cow::Borrowed(USER_AGENT_ORIGINAL),
+            request_class: Cow::Borrowed(REQUEST_CLASS),
         }
     }
 }
 
+impl LabelNames {
+    /// Returns the default and configured name of every label.
+    fn all(&self) -> Vec<(&'static str, &str)> {
+        let Self {
+            http_request_method,
+            server_address,
+            server_port,
+            error_type,
+            http_response_status,
+            http_response_status_class,
+            network_protocol_name,
+            network_protocol_version,
+            url_scheme,
+            http_route,
+            peer_service,
+            http_request_resend_count,
+            http_response_content_type,
+            trace_id,
+            network_transport,
+            url_full,
+            cache_hit,
+            outcome,
+            connection_reused,
+            operation,
+            tls_protocol_version,
+            http_client_name,
+            user_agent_original,
+            request_class,
+        } = self;
+        vec![
+            (HTTP_REQUEST_METHOD, http_request_method),
+            (SERVER_ADDRESS, server_address),
+            (SERVER_PORT, server_port),
+            (ERROR_TYPE, error_type),
+            (HTTP_RESPONSE_STATUS_CODE, http_response_status),
+            (HTTP_RESPONSE_STATUS_CLASS, http_response_status_class),
+            (NETWORK_PROTOCOL_NAME, network_protocol_name),
+            (NETWORK_PROTOCOL_VERSION, network_protocol_version),
+            (URL_SCHEME, url_scheme),
+            (HTTP_ROUTE, http_route),
+            (PEER_SERVICE, peer_service),
+            (HTTP_REQUEST_RESEND_COUNT, http_request_resend_count),
+            (
+                HTTP_RESPONSE_HEADER_CONTENT_TYPE,
+                http_response_content_type,
+            ),
+            (TRACE_ID, trace_id),
+            (NETWORK_TRANSPORT, network_transport),
+            (URL_FULL, url_full),
+            (CACHE_HIT, cache_hit),
+            (OUTCOME, outcome),
+            (CONNECTION_REUSED, connection_reused),
+            (OPERATION, operation),
+            (TLS_PROTOCOL_VERSION, tls_protocol_version),
+            (HTTP_CLIENT_NAME, http_client_name),
+            (USER_AGENT_ORIGINAL, user_agent_original),
+            (REQUEST_CLASS, request_class),
+        ]
+    }
+}
+
 impl Default for MetricsMiddleware {
     fn default() -> Self {
         Self::new()
@@ -157,6 +1489,82 @@ impl Default for MetricsMiddleware {
 #[derive(Debug, Clone)]
 pub struct MetricsMiddlewareBuilder {
     label_names: LabelNames,
+    metric_names: MetricNames,
+    duration_unit: DurationUnit,
+    body_size_unit: BodySizeUnit,
+    active_requests: bool,
+    request_body_size: bool,
+    response_body_size: bool,
+    status_class: bool,
+    url_template: Option<Hook<UrlTemplateFn>>,
+    peer_service: Option<String>,
+    constant_labels: Vec<(String, String)>,
+    dynamic_labels: Option<Hook<DynamicLabelsFn>>,
+    measure_response_body: bool,
+    skip_unknown_request_body_size: bool,
+    resend_count: Option<Hook<ResendCountFn>>,
+    connection_duration: bool,
+    recording_mode: RecordingMode,
+    gauge_summary_interval: Duration,
+    response_content_type: bool,
+    server_address_header: Option<HeaderName>,
+    server_port_header: Option<HeaderName>,
+    trace_id: bool,
+    skip_if: Option<Hook<SkipFn>>,
+    treat_4xx_as_error: bool,
+    network_transport: bool,
+    duration_buckets: Option<Vec<f64>>,
+    request_body_size_buckets: Option<Vec<f64>>,
+    response_body_size_buckets: Option<Vec<f64>>,
+    record_request_size_eagerly: bool,
+    omit_default_port: bool,
+    redirect_count: bool,
+    clock: Hook<dyn Clock>,
+    sink: Hook<dyn MetricsSink>,
+    collapse_unknown_methods: bool,
+    labels_from_request: Option<Hook<RequestLabelsFn>>,
+    url_full: bool,
+    url_redactor: Option<Hook<UrlRedactorFn>>,
+    request_size_hint: Option<Hook<RequestSizeHintFn>>,
+    cache_status: Option<Hook<CacheStatusFn>>,
+    #[cfg(feature = "tracing")]
+    tracing_events: Option<tracing::Level>,
+    network_protocol_name: Option<Cow<'static, str>>,
+    timeout_counter: bool,
+    sample_rate: f64,
+    dns_duration: bool,
+    omit_url_scheme: bool,
+    last_duration_gauge: bool,
+    error_type_fn: Option<Hook<ErrorTypeFn>>,
+    outcome: bool,
+    metric_prefix: Option<String>,
+    separate_connect_metrics: bool,
+    zero_head_response_body_size: bool,
+    on_complete: Option<Hook<OnCompleteFn>>,
+    detailed_error_type: bool,
+    omit_network_protocol_name: bool,
+    request_attempts: bool,
+    connection_reuse_label: bool,
+    operation_name: Option<Hook<OperationNameFn>>,
+    error_status_excludes: HashSet<StatusCode>,
+    total_duration_counter: bool,
+    tls_version_label: bool,
+    request_duration_description: Option<Cow<'static, str>>,
+    request_body_size_description: Option<Cow<'static, str>>,
+    response_body_size_description: Option<Cow<'static, str>>,
+    max_address_cardinality: Option<usize>,
+    client_name: Option<String>,
+    started_counter: bool,
+    slow_request_threshold: Option<Duration>,
+    user_agent: bool,
+    user_agent_map: Option<Hook<UserAgentMapFn>>,
+    header_count: bool,
+    response_header_count: bool,
+    strict_otel_labels: bool,
+    time_to_first_byte: bool,
+    treat_upgrades_specially: bool,
+    record_duration_always: bool,
+    status_counter: bool,
 }
 
 macro_rules! label_setters {
@@ -172,17 +1580,164 @@ macro_rules! label_setters {
         $(
             $(#[$attr])*
             pub fn $method_name<T: Into<String>>(&mut self, label: T) -> &mut Self {
-                self.label_names.$field_name = label.into();
+                self.label_names.$field_name = Cow::Owned(label.into());
                 self
             }
         )+
     };
 }
+
+macro_rules! label_getters {
+    (
+        $(
+            $(#[$attr:meta])*
+            $method_name:ident, $field_name:ident
+        );+
+        $(;)?
+    ) => {
+        $(
+            $(#[$attr])*
+            pub fn $method_name(&self) -> &str {
+                &self.inner.label_names.$field_name
+            }
+        )+
+    };
+}
+
+impl MetricsMiddleware {
+    label_getters! {
+        /// The name of the `http.request.method` label.
+        http_request_method_label, http_request_method;
+        /// The name of the `server.address` label.
+        server_address_label, server_address;
+        /// The name of the `server.port` label.
+        server_port_label, server_port;
+        /// The name of the `error.type` label.
+        error_type_label, error_type;
+        /// The name of the `http.response.status_code` label.
+        http_response_status_label, http_response_status;
+        /// The name of the `http.response.status_class` label.
+        http_response_status_class_label, http_response_status_class;
+        /// The name of the `network.protocol.name` label.
+        network_protocol_name_label, network_protocol_name;
+        /// The name of the `network.protocol.version` label.
+        network_protocol_version_label, network_protocol_version;
+        /// The name of the `url.scheme` label.
+        url_scheme_label, url_scheme;
+        /// The name of the `http.route` label.
+        http_route_label, http_route;
+        /// The name of the `peer.service` label.
+        peer_service_label, peer_service;
+        /// The name of the `http.request.resend_count` label.
+        http_request_resend_count_label, http_request_resend_count;
+        /// The name of the `http.response.header.content-type` label.
+        http_response_content_type_label, http_response_content_type;
+        /// The name of the `trace_id` label.
+        trace_id_label, trace_id;
+        /// The name of the `network.transport` label.
+        network_transport_label, network_transport;
+        /// The name of the `url.full` label.
+        url_full_label, url_full;
+        /// The name of the `cache.hit` label.
+        cache_hit_label, cache_hit;
+        /// The name of the `outcome` label.
+        outcome_label, outcome;
+        /// The name of the `connection.reused` label.
+        connection_reused_label, connection_reused;
+        /// The name of the `operation` label.
+        operation_label, operation;
+        /// The name of the `tls.protocol.version` label.
+        tls_protocol_version_label, tls_protocol_version;
+        /// The name of the `http.client.name` label.
+        http_client_name_label, http_client_name;
+        /// The name of the `user_agent.original` label.
+        user_agent_original_label, user_agent_original;
+        /// The name of the `request.class` label.
+        request_class_label, request_class
+    }
+}
+
 impl MetricsMiddlewareBuilder {
     /// Create a new [`MetricsMiddlewareBuilder`]
     pub fn new() -> Self {
         Self {
             label_names: LabelNames::default(),
+            metric_names: MetricNames::default(),
+            duration_unit: DurationUnit::default(),
+            body_size_unit: BodySizeUnit::default(),
+            active_requests: false,
+            request_body_size: true,
+            response_body_size: true,
+            status_class: false,
+            url_template: None,
+            peer_service: None,
+            constant_labels: Vec::new(),
+            dynamic_labels: None,
+            measure_response_body: false,
+            skip_unknown_request_body_size: false,
+            resend_count: None,
+            connection_duration: false,
+            recording_mode: RecordingMode::default(),
+            gauge_summary_interval: Duration::from_secs(60),
+            response_content_type: false,
+            server_address_header: None,
+            server_port_header: None,
+            trace_id: false,
+            skip_if: None,
+            treat_4xx_as_error: false,
+            network_transport: false,
+            duration_buckets: None,
+            request_body_size_buckets: None,
+            response_body_size_buckets: None,
+            record_request_size_eagerly: false,
+            omit_default_port: false,
+            redirect_count: false,
+            clock: Hook(Arc::new(SystemClock)),
+            sink: Hook(Arc::new(GlobalSink)),
+            collapse_unknown_methods: true,
+            labels_from_request: None,
+            url_full: false,
+            url_redactor: None,
+            request_size_hint: None,
+            cache_status: None,
+            #[cfg(feature = "tracing")]
+            tracing_events: None,
+            network_protocol_name: None,
+            timeout_counter: false,
+            sample_rate: 1.0,
+            dns_duration: false,
+            omit_url_scheme: false,
+            last_duration_gauge: false,
+            error_type_fn: None,
+            outcome: false,
+            metric_prefix: None,
+            separate_connect_metrics: false,
+            zero_head_response_body_size: false,
+            on_complete: None,
+            detailed_error_type: false,
+            omit_network_protocol_name: false,
+            request_attempts: false,
+            connection_reuse_label: false,
+            operation_name: None,
+            error_status_excludes: HashSet::new(),
+            total_duration_counter: false,
+            tls_version_label: false,
+            request_duration_description: None,
+            request_body_size_description: None,
+            response_body_size_description: None,
+            max_address_cardinality: None,
+            client_name: None,
+            started_counter: false,
+            slow_request_threshold: None,
+            user_agent: false,
+            user_agent_map: None,
+            header_count: false,
+            response_header_count: false,
+            strict_otel_labels: false,
+            time_to_first_byte: false,
+            treat_upgrades_specially: false,
+            record_duration_always: false,
+            status_counter: false,
         }
     }
 
@@ -197,158 +1752,2561 @@ impl MetricsMiddlewareBuilder {
         error_type_label, error_type;
         /// Rename the `http.response.status` label.
         http_response_status_label, http_response_status;
+        /// Rename the `http.response.status_class` label.
+        http_response_status_class_label, http_response_status_class;
         /// Rename the `network.protocol.name` label.
+        ///
+        /// Use [`MetricsMiddlewareBuilder::network_protocol_name_value`] to change its value.
         network_protocol_name_label, network_protocol_name;
         /// Rename the `network.protocol.version` label.
-        network_protocol_version_label, network_protocol_name;
+        network_protocol_version_label, network_protocol_version;
         /// Rename the `url.scheme` label.
-        url_scheme_label, url_scheme
-    }
-
-    /// Builds a [`MetricsMiddleware`]
-    pub fn build(&self) -> MetricsMiddleware {
-        MetricsMiddleware::new_inner(self.label_names.clone())
+        url_scheme_label, url_scheme;
+        /// Rename the `http.route` label.
+        http_route_label, http_route;
+        /// Rename the `peer.service` label.
+        peer_service_label, peer_service;
+        /// Rename the `http.request.resend_count` label.
+        http_request_resend_count_label, http_request_resend_count;
+        /// Rename the `http.response.header.content-type` label.
+        http_response_content_type_label, http_response_content_type;
+        /// Rename the `trace_id` label.
+        trace_id_label, trace_id;
+        /// Rename the `network.transport` label.
+        network_transport_label, network_transport;
+        /// Rename the `url.full` label.
+        url_full_label, url_full;
+        /// Rename the `cache.hit` label.
+        cache_hit_label, cache_hit;
+        /// Rename the `outcome` label.
+        outcome_label, outcome;
+        /// Rename the `connection.reused` label.
+        connection_reused_label, connection_reused;
+        /// Rename the `operation` label.
+        operation_label, operation;
+        /// Rename the `tls.protocol.version` label.
+        tls_protocol_version_label, tls_protocol_version;
+        /// Rename the `http.client.name` label.
+        http_client_name_label, http_client_name;
+        /// Rename the `user_agent.original` label.
+        user_agent_original_label, user_agent_original;
+        /// Rename the `request.class` label.
+        request_class_label, request_class
     }
-}
 
-impl Default for MetricsMiddlewareBuilder {
-    fn default() -> Self {
-        Self::new()
+    label_setters! {
+        /// Rename the `http.request.method` label.
+        /// Alias for [`MetricsMiddlewareBuilder::http_request_method_label`].
+        method_label, http_request_method;
+        /// Rename the `http.response.status` label.
+        /// Alias for [`MetricsMiddlewareBuilder::http_response_status_label`].
+        status_label, http_response_status;
+        /// Rename the `server.address` label.
+        /// Alias for [`MetricsMiddlewareBuilder::server_address_label`].
+        host_label, server_address
     }
-}
 
-#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
-#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
-impl Middleware for MetricsMiddleware {
-    async fn handle(
-        &self,
-        req: Request,
-        extensions: &mut Extensions,
-        next: Next<'_>,
-    ) -> Result<Response> {
-        let http_request_method = http_request_method(&req);
-        let url_scheme = url_scheme(&req);
-        let server_address = server_address(&req);
-        let server_port = server_port(&req);
-        let network_protocol_version = network_protocol_version(&req);
-        let request_body_size = req
-            .body()
-            .and_then(|body| body.as_bytes())
-            .map(|bytes| bytes.len())
-            .unwrap_or(0);
-
-        let start = Instant::now();
-        let res = next.run(req, extensions).await;
-        let duration = start.elapsed();
-
-        let mut labels = vec![
+    /// Rename every label provided in `config`, keeping the current name of the others.
+    ///
+    /// ```
+    /// use reqwest_metrics::{LabelNamesConfig, MetricsMiddleware};
+    ///
+    /// let metrics = MetricsMiddleware::builder()
+    ///     .with_label_names(LabelNamesConfig {
+    ///         http_request_method: Some("method".to_string()),
+    ///         http_response_status: Some("status".to_string()),
+    ///         ..Default::default()
+    ///     })
+    ///     .build();
+    /// assert_eq!(metrics.http_request_method_label(), "method");
+    /// assert_eq!(metrics.server_address_label(), "server.address");
+    /// ```
+    pub fn with_label_names(&mut self, config: LabelNamesConfig) -> &mut Self {
+        let LabelNamesConfig {
+            http_request_method,
+            server_address,
+            server_port,
+            error_type,
+            http_response_status,
+            http_response_status_class,
+            network_protocol_name,
+            network_protocol_version,
+            url_scheme,
+            http_route,
+            peer_service,
+            http_request_resend_count,
+            http_response_content_type,
+            trace_id,
+            network_transport,
+            url_full,
+            cache_hit,
+            outcome,
+            connection_reused,
+            operation,
+            tls_protocol_version,
+            http_client_name,
+            user_agent_original,
+            request_class,
+        } = config;
+        for (name, label) in [
             (
-                self.label_names.http_request_method.to_string(),
                 http_request_method,
+                &mut self.label_names.http_request_method,
             ),
-            (self.label_names.url_scheme.to_string(), url_scheme),
+            (server_address, &mut self.label_names.server_address),
+            (server_port, &mut self.label_names.server_port),
+            (error_type, &mut self.label_names.error_type),
             (
-                self.label_names.network_protocol_name.to_string(),
-                Cow::Borrowed("http"),
+                http_response_status,
+                &mut self.label_names.http_response_status,
             ),
-        ];
-
-        if let Some(server_address) = server_address {
-            labels.push((
-                self.label_names.server_address.to_string(),
-                Cow::Owned(server_address),
-            ));
+            (
+                http_response_status_class,
+                &mut self.label_names.http_response_status_class,
+            ),
+            (
+                network_protocol_name,
+                &mut self.label_names.network_protocol_name,
+            ),
+            (
+                network_protocol_version,
+                &mut self.label_names.network_protocol_version,
+            ),
+            (url_scheme, &mut self.label_names.url_scheme),
+            (http_route, &mut self.label_names.http_route),
+            (peer_service, &mut self.label_names.peer_service),
+            (
+                http_request_resend_count,
+                &mut self.label_names.http_request_resend_count,
+            ),
+            (
+                http_response_content_type,
+                &mut self.label_names.http_response_content_type,
+            ),
+            (trace_id, &mut self.label_names.trace_id),
+            (network_transport, &mut self.label_names.network_transport),
+            (url_full, &mut self.label_names.url_full),
+            (cache_hit, &mut self.label_names.cache_hit),
+            (outcome, &mut self.label_names.outcome),
+            (connection_reused, &mut self.label_names.connection_reused),
+            (operation, &mut self.label_names.operation),
+            (
+                tls_protocol_version,
+                &mut self.label_names.tls_protocol_version,
+            ),
+            (http_client_name, &mut self.label_names.http_client_name),
+            (
+                user_agent_original,
+                &mut self.label_names.user_agent_original,
+            ),
+            (request_class, &mut self.label_names.request_class),
+        ] {
+            if let Some(name) = name {
+                *label = Cow::Owned(name);
+            }
         }
+        self
+    }
 
-        if let Some(port) = server_port {
+    /// Prepend `prefix` to the names of all metrics, including renamed metrics.
+    ///
+    /// ```
+    /// use reqwest_metrics::MetricsMiddleware;
+    ///
+    /// let metrics = MetricsMiddleware::builder().metric_prefix("myapp_").build();
+    /// assert_eq!(
+    ///     "myapp_http.client.request.duration",
+    ///     metrics.request_duration_metric()
+    /// );
+    /// ```
+    pub fn metric_prefix<T: Into<String>>(&mut self, prefix: T) -> &mut Self {
+        self.metric_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Set the description of the `http.client.request.duration` metric.
+    ///
+    /// Metrics are described once per recorder, so this has no effect if another middleware
+    /// already described the metric. Descriptions are `'static` (or owned) since recorders keep
+    /// them for the lifetime of the metric.
+    pub fn request_duration_description(
+        &mut self,
+        description: impl Into<Cow<'static, str>>,
+    ) -> &mut Self {
+        self.request_duration_description = Some(description.into());
+        self
+    }
+
+    /// Set the description of the `http.client.request.body.size` metric.
+    ///
+    /// See [`MetricsMiddlewareBuilder::request_duration_description`] for its limitations.
+    pub fn request_body_size_description(
+        &mut self,
+        description: impl Into<Cow<'static, str>>,
+    ) -> &mut Self {
+        self.request_body_size_description = Some(description.into());
+        self
+    }
+
+    /// Set the description of the `http.client.response.body.size` metric.
+    ///
+    /// See [`MetricsMiddlewareBuilder::request_duration_description`] for its limitations.
+    pub fn response_body_size_description(
+        &mut self,
+        description: impl Into<Cow<'static, str>>,
+    ) -> &mut Self {
+        self.response_body_size_description = Some(description.into());
+        self
+    }
+
+    /// Rename the `http.client.request.count` metric.
+    pub fn counter_name<T: Into<String>>(&mut self, name: T) -> &mut Self {
+        self.metric_names.request_count = name.into();
+        self
+    }
+
+    /// Rename the `http.client.request.duration` metric.
+    pub fn request_duration_metric<T: Into<String>>(&mut self, name: T) -> &mut Self {
+        self.metric_names.request_duration = name.into();
+        self
+    }
+
+    /// Rename the `http.client.request.body.size` metric.
+    pub fn request_body_size_metric<T: Into<String>>(&mut self, name: T) -> &mut Self {
+        self.metric_names.request_body_size = name.into();
+        self
+    }
+
+    /// Rename the `http.client.response.body.size` metric.
+    pub fn response_body_size_metric<T: Into<String>>(&mut self, name: T) -> &mut Self {
+        self.metric_names.response_body_size = name.into();
+        self
+    }
+
+    /// Set the unit used for the `http.client.request.duration` histogram.
+    /// Defaults to [`DurationUnit::Seconds`].
+    pub fn duration_unit(&mut self, unit: DurationUnit) -> &mut Self {
+        self.duration_unit = unit;
+        self
+    }
+
+    /// Set the unit used for the `http.client.request.body.size` and
+    /// `http.client.response.body.size` histograms. Defaults to [`BodySizeUnit::Bytes`].
+    ///
+    /// Sizes are divided accordingly before being recorded, so this changes the magnitude of the
+    /// recorded values (and of the buckets to configure).
+    pub fn body_size_unit(&mut self, unit: BodySizeUnit) -> &mut Self {
+        self.body_size_unit = unit;
+        self
+    }
+
+    /// Set the buckets to use for the `http.client.request.duration` histogram, in the configured
+    /// [`DurationUnit`].
+    ///
+    /// `metrics` has no concept of buckets, so these are not applied automatically. Instead they
+    /// are exposed with [`MetricsMiddleware::duration_buckets`] to be passed to the exporter:
+    ///
+    /// ```
+    /// # use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+    /// # use reqwest_metrics::MetricsMiddleware;
+    /// let middleware = MetricsMiddleware::builder()
+    ///     .duration_buckets(vec![0.1, 0.25, 0.5, 1.0, 2.0, 5.0])
+    ///     .build();
+    ///
+    /// let mut builder = PrometheusBuilder::new();
+    /// if let Some(buckets) = middleware.duration_buckets() {
+    ///     builder = builder
+    ///         .set_buckets_for_metric(
+    ///             Matcher::Full(middleware.request_duration_metric().to_string()),
+    ///             buckets,
+    ///         )
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn duration_buckets(&mut self, buckets: Vec<f64>) -> &mut Self {
+        self.duration_buckets = Some(buckets);
+        self
+    }
+
+    /// Set the buckets to use for the `http.client.request.body.size` histogram, in the configured
+    /// [`BodySizeUnit`].
+    ///
+    /// Like [`MetricsMiddlewareBuilder::duration_buckets`], these are exposed with
+    /// [`MetricsMiddleware::request_body_size_buckets`] to be passed to the exporter.
+    pub fn request_body_size_buckets(&mut self, buckets: Vec<f64>) -> &mut Self {
+        self.request_body_size_buckets = Some(buckets);
+        self
+    }
+
+    /// Set the buckets to use for the `http.client.response.body.size` histogram, in the
+    /// configured [`BodySizeUnit`].
+    ///
+    /// Like [`MetricsMiddlewareBuilder::duration_buckets`], these are exposed with
+    /// [`MetricsMiddleware::response_body_size_buckets`] to be passed to the exporter.
+    pub fn response_body_size_buckets(&mut self, buckets: Vec<f64>) -> &mut Self {
+        self.response_body_size_buckets = Some(buckets);
+        self
+    }
+
+    /// Enable the `http.client.active_requests` gauge.
+    ///
+    /// The gauge only carries the `http.request.method`, `server.address` and `url.scheme` labels
+    /// (and `http.client.name`, if set with [`MetricsMiddlewareBuilder::client_name`]) since the
+    /// remaining labels are not known while the request is in flight.
+    pub fn enable_active_requests(&mut self) -> &mut Self {
+        self.active_requests = true;
+        self
+    }
+
+    /// Enable the `http.client.request.body.size` histogram. (enabled by default)
+    pub fn enable_request_body_size(&mut self) -> &mut Self {
+        self.request_body_size = true;
+        self
+    }
+
+    /// Disable the `http.client.request.body.size` histogram.
+    pub fn disable_request_body_size(&mut self) -> &mut Self {
+        self.request_body_size = false;
+        self
+    }
+
+    /// Enable the `http.client.response.body.size` histogram. (enabled by default)
+    pub fn enable_response_body_size(&mut self) -> &mut Self {
+        self.response_body_size = true;
+        self
+    }
+
+    /// Disable the `http.client.response.body.size` histogram.
+    pub fn disable_response_body_size(&mut self) -> &mut Self {
+        self.response_body_size = false;
+        self
+    }
+
+    /// Enable the `http.response.status_class` label (`1xx`, `2xx`, `3xx`, `4xx` or `5xx`).
+    ///
+    /// The label is recorded alongside `http.response.status_code` and is useful for alerting on
+    /// classes of responses without dealing with the cardinality of individual status codes.
+    pub fn enable_status_class(&mut self) -> &mut Self {
+        self.status_class = true;
+        self
+    }
+
+    /// Set a function to map request URLs to a low cardinality template (ie. `/users/{id}`)
+    /// which will be recorded as the `http.route` label.
+    ///
+    /// The label is omitted when the function returns `None`.
+    pub fn url_template_fn<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Url) -> Option<String> + Send + Sync + 'static,
+    {
+        self.url_template = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Set the logical name of the remote service (ie. `payments-api`), recorded as the
+    /// `peer.service` label on every request.
+    pub fn peer_service<T: Into<String>>(&mut self, service: T) -> &mut Self {
+        self.peer_service = Some(service.into());
+        self
+    }
+
+    /// Add a label with a fixed value to every metric (ie. `env=prod`).
+    ///
+    /// Multiple calls append labels rather than replacing previous ones.
+    /// Keeping the cardinality of these values low is the responsibility of the caller.
+    pub fn constant_label<K: Into<String>, V: Into<String>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> &mut Self {
+        self.constant_labels.push((key.into(), value.into()));
+        self
+    }
+
+    /// Set the name of the client, recorded as the `http.client.name` label on every metric (ie.
+    /// `payments`), to tell apart the metrics of clients sharing a recorder.
+    pub fn client_name<T: Into<String>>(&mut self, name: T) -> &mut Self {
+        self.client_name = Some(name.into());
+        self
+    }
+
+    /// Set a function to compute additional labels from the request [`Extensions`].
+    ///
+    /// This is useful for recording domain specific values that earlier middlewares stored in
+    /// the extensions (ie. an operation name). The function is called after the request
+    /// completes, so values inserted by later middlewares are visible as well.
+    pub fn dynamic_labels<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Extensions) -> Vec<(String, String)> + Send + Sync + 'static,
+    {
+        self.dynamic_labels = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Measure the actual number of bytes read from the response body rather than relying on the
+    /// `content-length` header.
+    ///
+    /// The response body is wrapped in a counting stream and `http.client.response.body.size` is
+    /// recorded once the body has been fully consumed. This adds a small amount of overhead to
+    /// every chunk read and bodies that are dropped before being fully read are not recorded.
+    ///
+    /// This has no effect on `wasm32` targets, where the `content-length` header is always used.
+    pub fn measure_response_body(&mut self) -> &mut Self {
+        self.measure_response_body = true;
+        self
+    }
+
+    /// Skip recording `http.client.request.body.size` when the size of a streaming request body
+    /// is unknown, instead of recording `0`.
+    ///
+    /// The size of streaming bodies is taken from the `content-length` header when present.
+    pub fn skip_unknown_request_body_size(&mut self) -> &mut Self {
+        self.skip_unknown_request_body_size = true;
+        self
+    }
+
+    /// Set a function to provide the size of request bodies whose size cannot be determined,
+    /// ie. bodies streamed from a file without a `content-length` header.
+    ///
+    /// The hint is only used when the body is not buffered in memory and has no `content-length`.
+    pub fn request_size_hint<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Request) -> Option<u64> + Send + Sync + 'static,
+    {
+        self.request_size_hint = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Set a function to read the number of times a request has been resent from the request
+    /// [`Extensions`], replacing the default lookup of [`ResendCount`].
+    ///
+    /// The `http.request.resend_count` label is omitted when the function returns `None` or `0`.
+    pub fn resend_count_fn<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Extensions) -> Option<u32> + Send + Sync + 'static,
+    {
+        self.resend_count = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Enable the `http.client.connection.duration` histogram.
+    ///
+    /// This is only recorded when a [`ConnectionDuration`] is available for the request.
+    pub fn enable_connection_duration(&mut self) -> &mut Self {
+        self.connection_duration = true;
+        self
+    }
+
+    /// Enable the `http.client.dns.duration` histogram.
+    ///
+    /// This is only recorded when a [`DnsDuration`] is available for the request.
+    pub fn enable_dns_duration(&mut self) -> &mut Self {
+        self.dns_duration = true;
+        self
+    }
+
+    /// Enable the `connection.reused` label, to tell apart requests sent on a fresh connection.
+    ///
+    /// The label is omitted when no [`ConnectionReused`] is available for the request.
+    pub fn enable_connection_reuse_label(&mut self) -> &mut Self {
+        self.connection_reuse_label = true;
+        self
+    }
+
+    /// Record at most `max` distinct `server.address` values, recording the addresses of any
+    /// other servers as `_OTHER`.
+    ///
+    /// This protects metrics backends from unbounded cardinality, ie. when a bug sends requests
+    /// to many hosts. Addresses are tracked by the built middleware and shared by its clones.
+    pub fn max_address_cardinality(&mut self, max: usize) -> &mut Self {
+        self.max_address_cardinality = Some(max);
+        self
+    }
+
+    /// Enable the `tls.protocol.version` label, ie. to audit the TLS versions negotiated with
+    /// servers.
+    ///
+    /// The label is omitted when no [`TlsVersion`] is available for the request, which is always
+    /// the case for plain `http` requests and on `wasm32`.
+    pub fn enable_tls_version_label(&mut self) -> &mut Self {
+        self.tls_version_label = true;
+        self
+    }
+
+    /// Enable the `http.client.request.attempts` and `http.client.request.attempt.duration`
+    /// histograms, for requests retried by a middleware placed after [`MetricsMiddleware`].
+    ///
+    /// These are only recorded when a [`RequestAttempts`] is available for the request.
+    pub fn enable_request_attempts(&mut self) -> &mut Self {
+        self.request_attempts = true;
+        self
+    }
+
+    /// Set how the histogram metrics are recorded.
+    /// Defaults to [`RecordingMode::Histogram`].
+    pub fn recording_mode(&mut self, mode: RecordingMode) -> &mut Self {
+        self.recording_mode = mode;
+        self
+    }
+
+    /// Set the interval summarized by the gauges of [`RecordingMode::GaugeSummary`]. Defaults to
+    /// 60 seconds.
+    ///
+    /// The first value recorded after an interval has elapsed starts a new summary, so the gauges
+    /// report the values of the current interval rather than since the process started. It should
+    /// be at least the scrape or push interval of the exporter, otherwise some values are never
+    /// exported.
+    pub fn gauge_summary_interval(&mut self, interval: Duration) -> &mut Self {
+        self.gauge_summary_interval = interval;
+        self
+    }
+
+    /// Enable the `http.response.header.content-type` label.
+    ///
+    /// Parameters are stripped from the header to keep the cardinality bounded, so
+    /// `application/json; charset=utf-8` is recorded as `application/json`. The label is omitted
+    /// when the request failed or the response has no `content-type` header.
+    pub fn enable_response_content_type(&mut self) -> &mut Self {
+        self.response_content_type = true;
+        self
+    }
+
+    /// Read the `server.address` label from the request header `header` (ie. `x-upstream-host`)
+    /// rather than the URL host, which is useful when requests are sent through a proxy.
+    ///
+    /// The URL host is used when the header is absent.
+    pub fn server_address_from_header(&mut self, header: HeaderName) -> &mut Self {
+        self.server_address_header = Some(header);
+        self
+    }
+
+    /// Read the `server.port` label from the request header `header` (ie. `x-upstream-port`)
+    /// rather than the URL port, which is useful when requests are sent through a proxy.
+    ///
+    /// The URL port (or the default port of its scheme) is used when the header is absent or is
+    /// not a valid port.
+    pub fn server_port_from_header(&mut self, header: HeaderName) -> &mut Self {
+        self.server_port_header = Some(header);
+        self
+    }
+
+    /// Enable the `trace_id` label, set to the trace ID of the active `tracing` span (through
+    /// `tracing-opentelemetry`) or OpenTelemetry context when the request is sent.
+    ///
+    /// Since `metrics` has no support for exemplars, this allows linking recordings to traces.
+    /// Every trace ID creates new time series, so this should only be used with backends that can
+    /// handle unbounded cardinality (ie. by turning the label into an exemplar). The label is
+    /// omitted when there is no active trace.
+    ///
+    /// The OpenTelemetry context is read with `opentelemetry` 0.33 and `tracing-opentelemetry`
+    /// 0.34, so the application must use the same versions: contexts of other versions are not
+    /// seen and the label is silently omitted.
+    #[cfg(feature = "otel-trace-id")]
+    pub fn enable_trace_id_label(&mut self) -> &mut Self {
+        self.trace_id = true;
+        self
+    }
+
+    /// Emit a `tracing` event at `level` for every completed request, with the same labels as the
+    /// recorded metrics and the duration of the request.
+    ///
+    /// This only needs the `tracing` feature, which does not depend on OpenTelemetry.
+    #[cfg(feature = "tracing")]
+    pub fn emit_tracing_events(&mut self, level: tracing::Level) -> &mut Self {
+        self.tracing_events = Some(level);
+        self
+    }
+
+    /// Set a predicate to skip recording metrics for some requests (ie. health checks).
+    ///
+    /// Skipped requests are still sent, but contribute to no metrics at all, including the body
+    /// size histograms and the `http.client.active_requests` gauge.
+    pub fn skip_if<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        self.skip_if = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Set whether `4xx` responses are recorded with an `error.type`. Defaults to `false`, so
+    /// only `5xx` responses and failed requests are treated as errors since `4xx` responses are
+    /// often expected (ie. a `404` when checking if a resource exists).
+    pub fn treat_4xx_as_error(&mut self, enabled: bool) -> &mut Self {
+        self.treat_4xx_as_error = enabled;
+        self
+    }
+
+    /// Never record an `error.type` for responses with one of the `statuses`, ie. for an API that
+    /// returns `404` as a normal "not found" response even with
+    /// [`MetricsMiddlewareBuilder::treat_4xx_as_error`] enabled.
+    ///
+    /// This also applies when a later middleware turns the response into an error with
+    /// [`Response::error_for_status`](reqwest_middleware::reqwest::Response::error_for_status).
+    ///
+    /// Statuses are added to the ones excluded by previous calls.
+    pub fn error_status_excludes(
+        &mut self,
+        statuses: impl IntoIterator<Item = StatusCode>,
+    ) -> &mut Self {
+        self.error_status_excludes.extend(statuses);
+        self
+    }
+
+    /// Enable the `network.transport` label, derived from the HTTP version of the request
+    /// (`quic` for HTTP/3 and `tcp` otherwise).
+    ///
+    /// The label is omitted on `wasm32` targets, where the HTTP version is not known.
+    pub fn enable_network_transport(&mut self) -> &mut Self {
+        self.network_transport = true;
+        self
+    }
+
+    /// Record `http.client.request.body.size` as soon as the request is sent rather than once it
+    /// completes, so body sizes are recorded regardless of the outcome of the request.
+    ///
+    /// Since the outcome is not known yet, the histogram only carries the
+    /// `http.request.method`, `server.address` and `url.scheme` labels.
+    pub fn record_request_size_eagerly(&mut self) -> &mut Self {
+        self.record_request_size_eagerly = true;
+        self
+    }
+
+    /// Set whether the `server.port` label is omitted when it is the default port of the scheme
+    /// (`80` for `http` and `443` for `https`), as recommended by Open Telemetry.
+    /// Defaults to `false`.
+    pub fn omit_default_port(&mut self, enabled: bool) -> &mut Self {
+        self.omit_default_port = enabled;
+        self
+    }
+
+    /// Enable the `http.client.request.last_duration` gauge, which is set to the duration of the
+    /// last request to each server, labeled with `server.address` (and `http.client.name`) only.
+    ///
+    /// This is meant for simple dashboards without histogram support. Concurrent requests to the
+    /// same server overwrite each other (the last to complete wins), so the gauge does not
+    /// reflect the latency distribution. Every server address creates a new time series.
+    pub fn enable_last_duration_gauge(&mut self) -> &mut Self {
+        self.last_duration_gauge = true;
+        self
+    }
+
+    /// Only record the labels OTel defines for each of its metrics (`http.client.request.duration`,
+    /// `http.client.active_requests`, `http.client.connection.duration` and the body size
+    /// histograms), rather than the same labels for every metric.
+    ///
+    /// By default, the opt-in labels of this crate (ie. `http.route`, `url.full` or
+    /// `http.client.name`) are recorded on every metric, and `http.client.connection.duration`
+    /// carries the labels of the request that opened the connection. With this option, these
+    /// labels are only recorded on the metrics of this crate (ie. `http.client.request.count`).
+    /// Custom labels (ie. [`MetricsMiddlewareBuilder::constant_label`]) are always recorded.
+    ///
+    /// Note that OTel does define `http.response.status_code` and `error.type` for
+    /// `http.client.request.body.size`, since it is recorded once the request completes. See
+    /// [`MetricsMiddlewareBuilder::record_request_size_eagerly`] to record it without them.
+    pub fn strict_otel_labels(&mut self) -> &mut Self {
+        self.strict_otel_labels = true;
+        self
+    }
+
+    /// Enable the `http.client.response.time_to_first_byte` histogram, recording the time until
+    /// the response headers were received.
+    ///
+    /// It is measured when the middlewares added after this one return, like
+    /// `http.client.request.duration`, but only for requests that received a response. Measuring
+    /// the response body (ie. with [`MetricsMiddlewareBuilder::measure_response_body`]) never
+    /// delays it. It carries the same labels as `http.client.request.duration`, also with
+    /// [`MetricsMiddlewareBuilder::strict_otel_labels`].
+    pub fn enable_time_to_first_byte(&mut self) -> &mut Self {
+        self.time_to_first_byte = true;
+        self
+    }
+
+    /// Enable the `http.client.request.header.count` histogram, recording the number of headers
+    /// of every request.
+    ///
+    /// Only headers set on the request are counted, the default headers of the client are added
+    /// after the middleware runs.
+    pub fn enable_header_count(&mut self) -> &mut Self {
+        self.header_count = true;
+        self
+    }
+
+    /// Enable the `http.client.response.header.count` histogram, recording the number of headers
+    /// of every response.
+    pub fn enable_response_header_count(&mut self) -> &mut Self {
+        self.response_header_count = true;
+        self
+    }
+
+    /// Enable the `http.client.request.slow.count` counter, which counts requests that took
+    /// longer than `threshold`, labeled with `http.request.method` and `server.address` (and
+    /// `http.client.name`).
+    ///
+    /// This allows alerting on slow requests without querying the histogram buckets.
+    pub fn slow_request_threshold(&mut self, threshold: Duration) -> &mut Self {
+        self.slow_request_threshold = Some(threshold);
+        self
+    }
+
+    /// Enable the `http.client.response.count` counter, which is incremented once per response
+    /// with only the `http.response.status_code` (and `http.client.name`) label.
+    ///
+    /// This is a compact alternative to the `_count` series of `http.client.request.duration` for
+    /// error rate panels. Requests that failed without a response are not counted.
+    pub fn enable_status_counter(&mut self) -> &mut Self {
+        self.status_counter = true;
+        self
+    }
+
+    /// Enable the `http.client.request.started` counter, which is incremented before a request is
+    /// sent, with the `http.request.method`, `url.scheme` and `server.address` labels.
+    ///
+    /// Comparing it with `http.client.request.count` reveals requests that are in flight or never
+    /// completed.
+    pub fn enable_started_counter(&mut self) -> &mut Self {
+        self.started_counter = true;
+        self
+    }
+
+    /// Enable the `http.client.request.duration.total` counter, which is incremented by the
+    /// duration of every request, with the same labels as `http.client.request.duration`.
+    ///
+    /// This is equivalent to the sum of the histogram, for backends that compute rates from
+    /// counters. `metrics` counters are integers, so the duration is counted in microseconds
+    /// regardless of [`MetricsMiddlewareBuilder::duration_unit`].
+    pub fn enable_total_duration_counter(&mut self) -> &mut Self {
+        self.total_duration_counter = true;
+        self
+    }
+
+    /// Enable the `outcome` label on the `http.client.request.count` counter, which is `error`
+    /// for failed requests and requests with a 5xx status (and 4xx with
+    /// [`MetricsMiddlewareBuilder::treat_4xx_as_error`]), and `success` otherwise.
+    pub fn enable_outcome_counter(&mut self) -> &mut Self {
+        self.outcome = true;
+        self
+    }
+
+    /// Set a function to call after the metrics of a completed request have been recorded, with
+    /// the recorded labels, the duration and the result of the request.
+    ///
+    /// This is not called for cancelled or panicking requests, nor by `MetricsLayer`.
+    pub fn on_complete<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&[(String, Cow<'static, str>)], Duration, &Result<Response>) + Send + Sync + 'static,
+    {
+        self.on_complete = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Record a `http.client.response.body.size` of `0` for `HEAD` requests.
+    ///
+    /// The `content-length` of a `HEAD` response describes the body a `GET` request would have
+    /// received, so by default no response body size is recorded for `HEAD` requests.
+    pub fn zero_head_response_body_size(&mut self) -> &mut Self {
+        self.zero_head_response_body_size = true;
+        self
+    }
+
+    /// Record the duration of `CONNECT` requests, which establish proxy tunnels, to the
+    /// `http.client.connect.duration` histogram instead of `http.client.request.duration`.
+    pub fn separate_connect_metrics(&mut self) -> &mut Self {
+        self.separate_connect_metrics = true;
+        self
+    }
+
+    /// Record the duration of requests upgraded to another protocol (ie. WebSocket handshakes
+    /// answered with `101 Switching Protocols`) to the `http.client.upgrade.duration` histogram
+    /// instead of `http.client.request.duration`, and set their `network.protocol.name` to the
+    /// protocol from the `upgrade` response header (ie. `websocket`).
+    ///
+    /// `101` responses are never errors and are always recorded with their status code.
+    pub fn treat_upgrades_specially(&mut self) -> &mut Self {
+        self.treat_upgrades_specially = true;
+        self
+    }
+
+    /// Set whether the `error.type` of failed requests is the full error message instead of a
+    /// category (`middleware` for middleware errors, and ie. `timeout` or `connect` for reqwest
+    /// errors). Defaults to `false`.
+    ///
+    /// Error messages often contain URLs or addresses, so this can create many time series.
+    pub fn detailed_error_type(&mut self, enabled: bool) -> &mut Self {
+        self.detailed_error_type = enabled;
+        self
+    }
+
+    /// Set a function to compute the `error.type` of a completed request, replacing the default
+    /// classification. The label is omitted when the function returns `None`.
+    ///
+    /// Cancelled and panicking requests are still recorded with an `error.type` of `cancelled`
+    /// and `panic`.
+    pub fn error_type_fn<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Result<Response>) -> Option<Cow<'static, str>> + Send + Sync + 'static,
+    {
+        self.error_type_fn = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Omit the `url.scheme` label, ie. for clients that only send `https` requests.
+    pub fn omit_url_scheme(&mut self) -> &mut Self {
+        self.omit_url_scheme = true;
+        self
+    }
+
+    /// Omit the `network.protocol.name` label, ie. for clients that only send HTTP requests.
+    pub fn omit_network_protocol_name(&mut self) -> &mut Self {
+        self.omit_network_protocol_name = true;
+        self
+    }
+
+    /// Enable the `http.client.redirect.count` counter, which counts requests that were
+    /// redirected.
+    ///
+    /// reqwest follows redirects within the client, so the redirect history is not visible to
+    /// middlewares. Instead a redirect is detected by comparing the URL of the final response
+    /// with the requested URL, so this counts redirected requests rather than individual
+    /// redirects, and redirects back to the requested URL are not detected.
+    ///
+    /// This is not supported by `MetricsLayer`, since `tower` responses do not carry their URL.
+    pub fn enable_redirect_count(&mut self) -> &mut Self {
+        self.redirect_count = true;
+        self
+    }
+
+    /// Enable the `http.client.request.timeouts` counter, which counts requests that failed with a
+    /// reqwest timeout, labeled with the method, scheme and address of the request.
+    ///
+    /// This is not supported by `MetricsLayer`, since `tower` errors are opaque.
+    pub fn enable_timeout_counter(&mut self) -> &mut Self {
+        self.timeout_counter = true;
+        self
+    }
+
+    /// Only record metrics for a random fraction of requests, between `0.0` and `1.0` (the
+    /// default), to reduce the overhead of recording for clients sending many requests.
+    ///
+    /// Requests that are not sampled are still sent, but contribute to no metrics at all. This
+    /// biases absolute values like counts, so sampling is meant for latency distributions only.
+    /// See [`MetricsMiddlewareBuilder::record_duration_always`] to record every duration.
+    pub fn sample_rate(&mut self, rate: f64) -> &mut Self {
+        self.sample_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set whether `http.client.request.duration` is recorded for every request, even those
+    /// that are not sampled by [`MetricsMiddlewareBuilder::sample_rate`]. Defaults to `false`.
+    ///
+    /// This keeps latency distributions accurate while the other metrics (ie. the body size
+    /// histograms) are sampled. Cancelled requests that are not sampled are not recorded.
+    pub fn record_duration_always(&mut self, always: bool) -> &mut Self {
+        self.record_duration_always = always;
+        self
+    }
+
+    /// Set the [`Clock`] used to measure the duration of requests. Defaults to [`SystemClock`].
+    pub fn with_clock(&mut self, clock: impl Clock) -> &mut Self {
+        self.clock = Hook(Arc::new(clock));
+        self
+    }
+
+    /// Set the [`MetricsSink`] metrics are recorded to. Defaults to [`GlobalSink`].
+    pub fn with_sink(&mut self, sink: impl MetricsSink) -> &mut Self {
+        self.sink = Hook(Arc::new(sink));
+        self
+    }
+
+    /// Set whether non-standard HTTP methods are recorded as `_OTHER` in the
+    /// `http.request.method` label, to keep its cardinality bounded as recommended by Open
+    /// Telemetry. Defaults to `true`.
+    pub fn collapse_unknown_methods(&mut self, enabled: bool) -> &mut Self {
+        self.collapse_unknown_methods = enabled;
+        self
+    }
+
+    /// Set a function to compute additional labels from the [`Request`] before it is sent.
+    ///
+    /// Labels with the same name as a label recorded by the middleware (ie. `server.address`)
+    /// replace its value, so this can also be used to override the default labels.
+    pub fn labels_from_request<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Request) -> Vec<(String, String)> + Send + Sync + 'static,
+    {
+        self.labels_from_request = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Enable the `user_agent.original` label, containing the `user-agent` header of the request.
+    /// The label is omitted when the header is absent.
+    ///
+    /// Only headers set on the request are visible to the middleware, the default headers of the
+    /// client (ie. set with `ClientBuilder::user_agent`) are added after it runs. User agents can
+    /// have a high cardinality, see [`MetricsMiddlewareBuilder::user_agent_map`] to bucket them.
+    pub fn enable_user_agent_label(&mut self) -> &mut Self {
+        self.user_agent = true;
+        self
+    }
+
+    /// Set a function to map the `user-agent` header to the value of the `user_agent.original`
+    /// label, ie. to bucket user agents into a few known values.
+    ///
+    /// This has no effect unless [`MetricsMiddlewareBuilder::enable_user_agent_label`] is
+    /// enabled.
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use reqwest_metrics::MetricsMiddleware;
+    ///
+    /// let metrics = MetricsMiddleware::builder()
+    ///     .enable_user_agent_label()
+    ///     .user_agent_map(|user_agent| match user_agent.split_once('/') {
+    ///         Some((product, _version)) => Cow::Owned(product.to_string()),
+    ///         None => Cow::Borrowed(user_agent),
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn user_agent_map<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&str) -> Cow<'_, str> + Send + Sync + 'static,
+    {
+        self.user_agent_map = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Enable the `url.full` label, containing the full URL of the request.
+    ///
+    /// URLs may contain sensitive data, so by default the query, fragment and credentials are
+    /// removed before the URL is recorded. Use [`MetricsMiddlewareBuilder::url_redactor`] to
+    /// redact URLs differently. Every distinct URL creates new time series, so this should only
+    /// be used for clients that send requests to a small set of URLs.
+    pub fn enable_uri(&mut self) -> &mut Self {
+        self.url_full = true;
+        self
+    }
+
+    /// Set a function to redact URLs before they are recorded in the `url.full` label, replacing
+    /// the default redaction.
+    pub fn url_redactor<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Url) -> String + Send + Sync + 'static,
+    {
+        self.url_redactor = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Set a function to read the name of the operation a request is part of from the
+    /// [`Extensions`], recorded as the `operation` label.
+    ///
+    /// The label is omitted when the function returns `None`.
+    ///
+    /// ```
+    /// use reqwest_metrics::MetricsMiddleware;
+    ///
+    /// #[derive(Clone)]
+    /// struct Operation(&'static str);
+    ///
+    /// let metrics = MetricsMiddleware::builder()
+    ///     .operation_name_extractor(|extensions| {
+    ///         extensions
+    ///             .get::<Operation>()
+    ///             .map(|operation| operation.0.to_string())
+    ///     })
+    ///     .build();
+    ///
+    /// // Then tag requests with `.with_extension(Operation("getUser"))`
+    /// ```
+    pub fn operation_name_extractor<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Extensions) -> Option<String> + Send + Sync + 'static,
+    {
+        self.operation_name = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Set a function to read whether a request was served from a cache from the [`Extensions`],
+    /// ie. as marked by a caching middleware, recorded as the `cache.hit` label (`true`/`false`).
+    ///
+    /// The label is omitted when the function returns `None`.
+    pub fn cache_status_extractor<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&Extensions) -> Option<bool> + Send + Sync + 'static,
+    {
+        self.cache_status = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Set the value of the `network.protocol.name` label, ie. for clients tunneling a non-HTTP
+    /// protocol. By default it is derived from the URL scheme (`websocket` for `ws` and `wss`,
+    /// `http` otherwise).
+    pub fn network_protocol_name_value(&mut self, name: impl Into<Cow<'static, str>>) -> &mut Self {
+        self.network_protocol_name = Some(name.into());
+        self
+    }
+
+    /// Builds a [`MetricsMiddleware`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if any configured buckets are invalid, see [`MetricsMiddlewareBuilder::try_build`]
+    /// to handle invalid configurations instead.
+    pub fn build(&self) -> MetricsMiddleware {
+        if let Err(err) = self.check_buckets() {
+            panic!("{err}");
+        }
+        MetricsMiddleware::new_inner(self)
+    }
+
+    /// Builds a [`MetricsMiddleware`], checking the configuration first.
+    ///
+    /// Returns an error if two labels were renamed to the same name, since most exporters reject
+    /// (or mishandle) metrics with duplicate label names, or if any configured buckets are empty,
+    /// not sorted or `NaN`.
+    pub fn try_build(&self) -> std::result::Result<MetricsMiddleware, BuilderError> {
+        let labels = self.label_names.all();
+        for (i, (label, name)) in labels.iter().enumerate() {
+            if let Some((other, _)) = labels[..i].iter().find(|(_, other)| other == name) {
+                return Err(BuilderError::DuplicateLabelName {
+                    name: name.to_string(),
+                    labels: (other, label),
+                });
+            }
+        }
+
+        self.check_buckets()?;
+        Ok(MetricsMiddleware::new_inner(self))
+    }
+
+    /// Checks that the configured buckets are non-empty, sorted and not `NaN`.
+    fn check_buckets(&self) -> std::result::Result<(), BuilderError> {
+        for (metric, buckets) in [
+            (&self.metric_names.request_duration, &self.duration_buckets),
+            (
+                &self.metric_names.request_body_size,
+                &self.request_body_size_buckets,
+            ),
+            (
+                &self.metric_names.response_body_size,
+                &self.response_body_size_buckets,
+            ),
+        ] {
+            if let Some(buckets) = buckets {
+                if buckets.is_empty()
+                    || buckets.iter().any(|bucket| bucket.is_nan())
+                    || !buckets.windows(2).all(|pair| pair[0] < pair[1])
+                {
+                    return Err(BuilderError::InvalidBuckets {
+                        metric: metric.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Error returned by [`MetricsMiddlewareBuilder::try_build`] for invalid configurations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuilderError {
+    /// Two labels, identified by their default names, were given the same name.
+    DuplicateLabelName {
+        /// The duplicated name.
+        name: String,
+        /// The default names of the conflicting labels.
+        labels: (&'static str, &'static str),
+    },
+    /// The buckets configured for a histogram are empty, not sorted in increasing order or `NaN`.
+    InvalidBuckets {
+        /// The name of the histogram.
+        metric: String,
+    },
+    /// An environment variable read by [`MetricsMiddleware::from_env`] is empty or not valid
+    /// unicode.
+    InvalidEnvVar {
+        /// The name of the variable.
+        name: &'static str,
+    },
+    /// [`MetricsMiddleware::set_global_defaults`] was called more than once.
+    GlobalDefaultsAlreadySet,
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuilderError::DuplicateLabelName { name, labels } => write!(
+                f,
+                "the `{}` and `{}` labels are both named `{name}`",
+                labels.0, labels.1
+            ),
+            BuilderError::InvalidBuckets { metric } => write!(
+                f,
+                "the buckets of `{metric}` must be non-empty, not `NaN` and sorted in increasing order"
+            ),
+            BuilderError::InvalidEnvVar { name } => {
+                write!(
+                    f,
+                    "the `{name}` environment variable must be a non-empty string"
+                )
+            }
+            BuilderError::GlobalDefaultsAlreadySet => {
+                write!(f, "the global defaults have already been set")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuilderError {}
+
+impl Default for MetricsMiddlewareBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for MetricsMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        if cfg!(feature = "disabled") || self.skip(&req) {
+            return next.run(req, extensions).await;
+        }
+        if !self.sampled() {
+            if !self.inner.record_duration_always {
+                return next.run(req, extensions).await;
+            }
+
+            let request_labels = self.request_labels(&req, true);
+            let start = self.inner.clock.0.now();
+            let res = next.run(req, extensions).await;
+            let duration = self.inner.clock.0.now().saturating_duration_since(start);
+            let labels =
+                self.completed_labels(request_labels, &self.outcome(&res, extensions, None));
+            self.record_duration(&labels, duration);
+            return res;
+        }
+
+        let request_labels = self.request_labels(&req, true);
+        let request_body_size = self.request_size(&req);
+        let url = self.inner.redirect_count.then(|| req.url().clone());
+        let head = req.method() == Method::HEAD;
+        let request_header_count = req.headers().len();
+
+        let mut in_flight = InFlight::new(self, request_labels, request_body_size);
+        let res = {
+            let mut run = pin!(next.run(req, extensions));
+            // Panics are recorded before unwinding, since executors usually catch the panic
+            // before dropping the future (at which point it would look like a cancellation).
+            poll_fn(
+                |cx| match panic::catch_unwind(AssertUnwindSafe(|| run.as_mut().poll(cx))) {
+                    Ok(poll) => poll,
+                    Err(panic) => {
+                        in_flight.abort("panic");
+                        panic::resume_unwind(panic)
+                    }
+                },
+            )
+            .await
+        };
+        let (request_labels, duration) = in_flight.finish();
+
+        let outcome = self.outcome(&res, extensions, url.as_ref());
+        let (failed, redirected) = (outcome.failed, outcome.redirected);
+        let labels = self.record_completed(
+            request_labels,
+            in_flight.request_body_size,
+            request_header_count,
+            &outcome,
+            duration,
+        );
+
+        let res = if self.inner.response_body_size && !head {
+            let histogram = self.histogram(
+                &self.inner.metric_names.response_body_size,
+                &self.otel_labels(&labels, &OTEL_REQUEST_LABELS),
+            );
+            self.record_response_body_size(res, histogram)
+        } else {
+            if self.inner.response_body_size && self.inner.zero_head_response_body_size {
+                self.histogram(
+                    &self.inner.metric_names.response_body_size,
+                    &self.otel_labels(&labels, &OTEL_REQUEST_LABELS),
+                )
+                .record(0.0);
+            }
+            res
+        };
+
+        self.record_counters(&labels, failed, redirected);
+
+        if let Some(on_complete) = &self.inner.on_complete {
+            (on_complete.0)(&owned_keys(&labels), duration, &res);
+        }
+
+        res
+    }
+}
+
+/// Allows sharing a single [`MetricsMiddleware`] (ie. stored in a `static`) between many clients
+/// without cloning its configuration.
+///
+/// A middleware behind an [`Arc`] can be shared with [`ClientBuilder::with_arc`] instead.
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for &'static MetricsMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        (*self).handle(req, extensions, next).await
+    }
+}
+
+/// Guard for a request that has been sent, which records the request with an `error.type` of
+/// `cancelled` (or `panic`) if it is dropped before [`InFlight::finish`] is called.
+///
+/// This makes sure requests aborted by callers (ie. with a timeout) are still recorded.
+struct InFlight<M: Deref<Target = MetricsMiddleware>> {
+    metrics: M,
+    request: Option<RequestLabels>,
+    request_body_size: Option<u64>,
+    active_requests: Option<Gauge>,
+    start: Instant,
+}
+
+impl<M: Deref<Target = MetricsMiddleware>> InFlight<M> {
+    fn new(metrics: M, request: RequestLabels, request_body_size: Option<u64>) -> Self {
+        let request_body_size =
+            metrics.record_request_body_size_eagerly(&request, request_body_size);
+        let active_requests = metrics.active_requests_gauge(&request);
+        if let Some(active_requests) = &active_requests {
+            active_requests.increment(1.0);
+        }
+        if metrics.inner.started_counter {
+            metrics
+                .counter(
+                    &metrics.inner.metric_names.request_started,
+                    &metrics.in_flight_labels(&request),
+                )
+                .increment(1);
+        }
+        let start = metrics.inner.clock.0.now();
+        Self {
+            metrics,
+            request: Some(request),
+            request_body_size,
+            active_requests,
+            start,
+        }
+    }
+
+    /// Marks the request as completed, returning its labels and duration to be recorded by the
+    /// caller.
+    fn finish(&mut self) -> (RequestLabels, Duration) {
+        let duration = self.elapsed();
+        self.decrement_active_requests();
+        let request = self.request.take().expect("request is only finished once");
+        (request, duration)
+    }
+
+    /// Records the request as failed with `error_type`, unless it already finished.
+    fn abort(&mut self, error_type: &'static str) {
+        let Some(mut request) = self.request.take() else {
+            return;
+        };
+        let duration = self.elapsed();
+        self.decrement_active_requests();
+
+        let custom = mem::take(&mut request.custom);
+        let mut labels = self
+            .metrics
+            .labels(request, None, Some(Cow::Borrowed(error_type)));
+        push_custom_labels(&mut labels, custom);
+        self.metrics
+            .record_request(&labels, duration, self.request_body_size, None, None);
+        self.metrics.increment_request_count(&labels, true);
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.metrics
+            .inner
+            .clock
+            .0
+            .now()
+            .saturating_duration_since(self.start)
+    }
+
+    fn decrement_active_requests(&mut self) {
+        if let Some(active_requests) = self.active_requests.take() {
+            active_requests.decrement(1.0);
+        }
+    }
+}
+
+impl<M: Deref<Target = MetricsMiddleware>> Drop for InFlight<M> {
+    fn drop(&mut self) {
+        self.abort(if thread::panicking() {
+            "panic"
+        } else {
+            "cancelled"
+        });
+    }
+}
+
+/// Label values that are known before a request is sent.
+struct RequestLabels {
+    http_request_method: Cow<'static, str>,
+    url_scheme: Cow<'static, str>,
+    server_address: Option<String>,
+    server_port: Option<u16>,
+    network_protocol_version: Option<&'static str>,
+    http_route: Option<String>,
+    url_full: Option<String>,
+    user_agent: Option<String>,
+    trace_id: Option<String>,
+    custom: Vec<(String, String)>,
+}
+
+/// The result of a completed request in plain `http` types, so the middleware and `MetricsLayer`
+/// record it the same way.
+struct Outcome<'a> {
+    /// The status of the response, which is also available for some errors.
+    status: Option<StatusCode>,
+    /// The headers and (if available) extensions of the response, if one was received.
+    response: Option<(&'a HeaderMap, Option<&'a Extensions>)>,
+    /// The request [`Extensions`].
+    extensions: &'a Extensions,
+    error_type: Option<Cow<'static, str>>,
+    /// Whether the request failed, including responses with an error status.
+    failed: bool,
+    timed_out: bool,
+    redirected: bool,
+}
+
+impl Outcome<'_> {
+    /// Looks up an extension on the response, falling back to the request [`Extensions`].
+    fn extension<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        self.response
+            .and_then(|(_, extensions)| extensions?.get::<T>())
+            .or_else(|| self.extensions.get::<T>())
+            .cloned()
+    }
+}
+
+impl MetricsMiddleware {
+    /// Returns the `http.client.active_requests` gauge for a request, if enabled.
+    fn active_requests_gauge(&self, request: &RequestLabels) -> Option<Gauge> {
+        if !self.inner.active_requests {
+            return None;
+        }
+
+        let labels = self.in_flight_labels(request);
+        Some(self.gauge(
+            &self.inner.metric_names.active_requests,
+            &self.otel_labels(&labels, &OTEL_ACTIVE_REQUESTS_LABELS),
+        ))
+    }
+
+    /// Records the request body size before the request is sent, if enabled. Returns the size
+    /// that is left to be recorded once the request completes.
+    fn record_request_body_size_eagerly(
+        &self,
+        request: &RequestLabels,
+        request_body_size: Option<u64>,
+    ) -> Option<u64> {
+        if !(self.inner.request_body_size && self.inner.record_request_size_eagerly) {
+            return request_body_size;
+        }
+
+        if let Some(request_body_size) = request_body_size {
+            let labels = self.in_flight_labels(request);
+            self.histogram(
+                &self.inner.metric_names.request_body_size,
+                &self.otel_labels(&labels, &OTEL_REQUEST_LABELS),
+            )
+            .record(self.inner.body_size_unit.value(request_body_size));
+        }
+        None
+    }
+
+    /// Builds the labels for metrics recorded while the request is in flight.
+    fn in_flight_labels(
+        &self,
+        request: &RequestLabels,
+    ) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        let mut labels: Vec<_> = self.client_name_label().into_iter().collect();
+        labels.push((
+            self.inner.label_names.http_request_method.clone(),
+            request.http_request_method.clone(),
+        ));
+        if !self.inner.omit_url_scheme {
+            labels.push((
+                self.inner.label_names.url_scheme.clone(),
+                request.url_scheme.clone(),
+            ));
+        }
+        if let Some(server_address) = &request.server_address {
+            labels.push((
+                self.inner.label_names.server_address.clone(),
+                Cow::Owned(server_address.clone()),
+            ));
+        }
+        labels
+    }
+
+    /// Builds the `http.client.name` label, if a client name is set.
+    fn client_name_label(&self) -> Option<(Cow<'static, str>, Cow<'static, str>)> {
+        let client_name = self.inner.client_name.as_ref()?;
+        Some((
+            self.inner.label_names.http_client_name.clone(),
+            Cow::Owned(client_name.clone()),
+        ))
+    }
+
+    /// Returns the `http.request.method` label value for `method`.
+    fn http_request_method(&self, method: &Method) -> Cow<'static, str> {
+        match known_http_request_method(method) {
+            Some(method) => Cow::Borrowed(method),
+            None if self.inner.collapse_unknown_methods => Cow::Borrowed("_OTHER"),
+            None => Cow::Owned(method.as_str().to_string()),
+        }
+    }
+
+    /// Returns the `error.type` of a completed request, if it failed.
+    fn error_type(&self, res: &Result<Response>) -> Option<Cow<'static, str>> {
+        if let Some(error_type_fn) = &self.inner.error_type_fn {
+            return (error_type_fn.0)(res);
+        }
+
+        Some(match res {
+            Ok(res) => return self.status_error_type(res.status()),
+            Err(err) if self.excluded_error_status(err) => return None,
+            Err(err) if self.inner.detailed_error_type => Cow::Owned(err.to_string()),
+            Err(Error::Middleware(_)) => Cow::Borrowed("middleware"),
+            Err(Error::Reqwest(err)) => Cow::Borrowed(reqwest_error_type(err)),
+        })
+    }
+
+    /// Returns whether an error was only derived from a response status (ie. by
+    /// `error_for_status` in a later middleware) excluded by
+    /// [`MetricsMiddlewareBuilder::error_status_excludes`].
+    fn excluded_error_status(&self, err: &Error) -> bool {
+        matches!(err, Error::Reqwest(err) if err
+            .status()
+            .is_some_and(|status| self.inner.error_status_excludes.contains(&status)))
+    }
+
+    /// Returns the `error.type` of a response, if its status is considered an error.
+    fn status_error_type(&self, status: StatusCode) -> Option<Cow<'static, str>> {
+        if self.inner.error_status_excludes.contains(&status) {
+            None
+        } else if status.is_server_error()
+            || (self.inner.treat_4xx_as_error && status.is_client_error())
+        {
+            Some(Cow::Owned(status.as_str().to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether no metrics should be recorded for a request, regardless of sampling.
+    fn skip(&self, req: &Request) -> bool {
+        self.inner
+            .skip_if
+            .as_ref()
+            .is_some_and(|skip_if| (skip_if.0)(req))
+    }
+
+    /// Builds the labels known before a request is sent, counting its `server.address` towards
+    /// [`MetricsMiddlewareBuilder::max_address_cardinality`] if `record_address` is set.
+    fn request_labels(&self, req: &Request, record_address: bool) -> RequestLabels {
+        RequestLabels {
+            http_request_method: self.http_request_method(req.method()),
+            url_scheme: url_scheme(req.url().scheme()),
+            server_address: self.bounded_server_address(
+                self.server_address_from_header(req.headers())
+                    .or_else(|| server_address(req)),
+                record_address,
+            ),
+            server_port: self
+                .server_port_from_header(req.headers())
+                .or_else(|| server_port(req)),
+            network_protocol_version: network_protocol_version(req),
+            http_route: self
+                .inner
+                .url_template
+                .as_ref()
+                .and_then(|url_template| (url_template.0)(req.url())),
+            url_full: self.url_full(req.url()),
+            user_agent: self.user_agent(req.headers()),
+            trace_id: self.trace_id(),
+            custom: self
+                .inner
+                .labels_from_request
+                .as_ref()
+                .map(|labels_from_request| (labels_from_request.0)(req))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns the request body size to record, if any.
+    fn request_size(&self, req: &Request) -> Option<u64> {
+        request_body_size(req)
+            .or_else(|| {
+                self.inner
+                    .request_size_hint
+                    .as_ref()
+                    .and_then(|request_size_hint| (request_size_hint.0)(req))
+            })
+            .or_else(|| (!self.inner.skip_unknown_request_body_size).then_some(0))
+    }
+
+    /// Describes a completed request for [`MetricsMiddleware::record_completed`].
+    fn outcome<'a>(
+        &self,
+        res: &'a Result<Response>,
+        extensions: &'a Extensions,
+        url: Option<&Url>,
+    ) -> Outcome<'a> {
+        Outcome {
+            status: response_status(res),
+            response: res
+                .as_ref()
+                .ok()
+                .map(|res| (res.headers(), response_extensions(res))),
+            extensions,
+            error_type: self.error_type(res),
+            failed: match res {
+                Ok(res) => self.status_error_type(res.status()).is_some(),
+                Err(err) => !self.excluded_error_status(err),
+            },
+            timed_out: matches!(res, Err(Error::Reqwest(err)) if err.is_timeout()),
+            redirected: matches!((url, res), (Some(url), Ok(res)) if res.url() != url),
+        }
+    }
+
+    /// Builds the labels of a completed request.
+    fn completed_labels(
+        &self,
+        mut request_labels: RequestLabels,
+        outcome: &Outcome<'_>,
+    ) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        let custom = mem::take(&mut request_labels.custom);
+        let mut labels = self.labels(request_labels, outcome.status, outcome.error_type.clone());
+        if let (Some(status), Some((headers, _))) = (outcome.status, outcome.response) {
+            self.upgrade_protocol_name(&mut labels, status, headers);
+            labels.extend(self.response_labels(headers));
+        }
+        labels.extend(self.extension_labels(outcome.extensions));
+        labels.extend(self.connection_reused(outcome.extension()));
+        labels.extend(self.tls_version(outcome.extension()));
+        push_custom_labels(
+            &mut labels,
+            custom
+                .into_iter()
+                .chain(self.dynamic_labels(outcome.extensions)),
+        );
+        labels
+    }
+
+    /// Records the duration and request metrics of a completed request, returning its labels.
+    ///
+    /// This is shared by the middleware and `MetricsLayer`, which record the response body size
+    /// and then [`MetricsMiddleware::record_counters`] themselves.
+    fn record_completed(
+        &self,
+        request_labels: RequestLabels,
+        request_body_size: Option<u64>,
+        request_header_count: usize,
+        outcome: &Outcome<'_>,
+        duration: Duration,
+    ) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        if self.inner.timeout_counter && outcome.timed_out {
+            self.counter(
+                &self.inner.metric_names.request_timeouts,
+                &self.in_flight_labels(&request_labels),
+            )
+            .increment(1);
+        }
+
+        let labels = self.completed_labels(request_labels, outcome);
+
+        self.record_request(
+            &labels,
+            duration,
+            request_body_size,
+            outcome.extension(),
+            outcome.extension(),
+        );
+
+        if self.inner.time_to_first_byte && outcome.response.is_some() {
+            self.histogram(
+                &self.inner.metric_names.time_to_first_byte,
+                &self.otel_labels(&labels, &OTEL_REQUEST_LABELS),
+            )
+            .record(self.inner.duration_unit.value(duration));
+        }
+
+        if self.inner.request_attempts {
+            if let Some(attempts) = outcome.extension::<RequestAttempts>() {
+                self.histogram(&self.inner.metric_names.request_attempts, &labels)
+                    .record(attempts.count as f64);
+                let attempt_duration =
+                    self.histogram(&self.inner.metric_names.attempt_duration, &labels);
+                for duration in &attempts.durations {
+                    attempt_duration.record(self.inner.duration_unit.value(*duration));
+                }
+            }
+        }
+
+        self.record_header_counts(
+            &labels,
+            request_header_count,
+            outcome.response.map(|(headers, _)| headers.len()),
+        );
+
+        labels
+    }
+
+    /// Records the number of request and response headers, if enabled.
+    fn record_header_counts(
+        &self,
+        labels: &[(Cow<'static, str>, Cow<'static, str>)],
+        request_header_count: usize,
+        response_header_count: Option<usize>,
+    ) {
+        if self.inner.header_count {
+            self.histogram(&self.inner.metric_names.request_header_count, labels)
+                .record(request_header_count as f64);
+        }
+        if self.inner.response_header_count {
+            if let Some(response_header_count) = response_header_count {
+                self.histogram(&self.inner.metric_names.response_header_count, labels)
+                    .record(response_header_count as f64);
+            }
+        }
+    }
+
+    /// Increments the `http.client.request.count` counter, with the `outcome` label if enabled.
+    fn increment_request_count(
+        &self,
+        labels: &[(Cow<'static, str>, Cow<'static, str>)],
+        failed: bool,
+    ) {
+        let counter = if self.inner.outcome {
+            let mut labels = labels.to_vec();
+            labels.push((
+                self.inner.label_names.outcome.clone(),
+                Cow::Borrowed(if failed { "error" } else { "success" }),
+            ));
+            self.counter(&self.inner.metric_names.request_count, &labels)
+        } else {
+            self.counter(&self.inner.metric_names.request_count, labels)
+        };
+        counter.increment(1);
+    }
+
+    /// Increments the `http.client.response.count` counter, if enabled and a response was received.
+    fn increment_response_count(&self, labels: &[(Cow<'static, str>, Cow<'static, str>)]) {
+        if !self.inner.status_counter {
+            return;
+        }
+
+        if !labels
+            .iter()
+            .any(|(key, _)| *key == self.inner.label_names.http_response_status)
+        {
+            return;
+        }
+
+        let status: Vec<_> = labels
+            .iter()
+            .filter(|(key, _)| {
+                *key == self.inner.label_names.http_response_status
+                    || *key == self.inner.label_names.http_client_name
+            })
+            .cloned()
+            .collect();
+        self.counter(&self.inner.metric_names.response_count, &status)
+            .increment(1);
+    }
+
+    /// Records the response body size read from the response headers (or `0`), or none for
+    /// `HEAD` requests unless [`MetricsMiddlewareBuilder::zero_head_response_body_size`] is set.
+    fn record_known_response_body_size(
+        &self,
+        labels: &[(Cow<'static, str>, Cow<'static, str>)],
+        head: bool,
+        response_body_size: Option<u64>,
+    ) {
+        if !self.inner.response_body_size {
+            return;
+        }
+
+        let response_body_size = if head {
+            self.inner.zero_head_response_body_size.then_some(0)
+        } else {
+            Some(response_body_size.unwrap_or(0))
+        };
+        if let Some(response_body_size) = response_body_size {
+            self.histogram(
+                &self.inner.metric_names.response_body_size,
+                &self.otel_labels(labels, &OTEL_REQUEST_LABELS),
+            )
+            .record(self.inner.body_size_unit.value(response_body_size));
+        }
+    }
+
+    /// Records the counters of a completed request, after its response body size.
+    fn record_counters(
+        &self,
+        labels: &[(Cow<'static, str>, Cow<'static, str>)],
+        failed: bool,
+        redirected: bool,
+    ) {
+        self.increment_request_count(labels, failed);
+        self.increment_response_count(labels);
+
+        if redirected {
+            self.counter(&self.inner.metric_names.redirect_count, labels)
+                .increment(1);
+        }
+    }
+
+    /// Returns whether metrics should be recorded for a request, according to the sample rate.
+    fn sampled(&self) -> bool {
+        self.inner.sample_rate >= 1.0 || random() < self.inner.sample_rate
+    }
+
+    /// Returns the redacted `url.full` of a request, if enabled.
+    fn url_full(&self, url: &Url) -> Option<String> {
+        if !self.inner.url_full {
+            return None;
+        }
+
+        Some(match &self.inner.url_redactor {
+            Some(url_redactor) => (url_redactor.0)(url),
+            None => redact_url(url),
+        })
+    }
+
+    /// Returns the `user_agent.original` label value of a request, if enabled.
+    fn user_agent(&self, headers: &HeaderMap) -> Option<String> {
+        if !self.inner.user_agent {
+            return None;
+        }
+
+        let user_agent = headers.get(USER_AGENT)?.to_str().ok()?;
+        Some(match &self.inner.user_agent_map {
+            Some(user_agent_map) => (user_agent_map.0)(user_agent).into_owned(),
+            None => user_agent.to_string(),
+        })
+    }
+
+    /// Returns the trace ID of the active span, if enabled.
+    fn trace_id(&self) -> Option<String> {
+        #[cfg(feature = "otel-trace-id")]
+        if self.inner.trace_id {
+            return current_trace_id();
+        }
+        None
+    }
+
+    /// Replaces `address` with `_OTHER` once the maximum number of addresses has been recorded,
+    /// recording `address` itself if `record` is set.
+    fn bounded_server_address(&self, address: Option<String>, record: bool) -> Option<String> {
+        match &self.inner.seen_addresses {
+            Some(seen_addresses) => address.map(|address| seen_addresses.bound(address, record)),
+            None => address,
+        }
+    }
+
+    /// Returns the `server.address` from the configured request header, if any.
+    fn server_address_from_header(&self, headers: &HeaderMap) -> Option<String> {
+        let header = self.inner.server_address_header.as_ref()?;
+        let value = headers.get(header)?.to_str().ok()?;
+        Some(lowercase_host(value))
+    }
+
+    /// Returns the `server.port` from the configured request header, if any and valid.
+    fn server_port_from_header(&self, headers: &HeaderMap) -> Option<u16> {
+        let header = self.inner.server_port_header.as_ref()?;
+        headers.get(header)?.to_str().ok()?.trim().parse().ok()
+    }
+
+    /// Builds the labels for a completed request.
+    fn labels(
+        &self,
+        request: RequestLabels,
+        status: Option<StatusCode>,
+        error_type: Option<Cow<'static, str>>,
+    ) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        let mut labels: Vec<(Cow<'static, str>, Cow<'static, str>)> = self
+            .inner
+            .constant_labels
+            .iter()
+            .map(|(key, value)| (Cow::Owned(key.clone()), Cow::Owned(value.clone())))
+            .collect();
+        labels.extend(self.client_name_label());
+
+        let server_port = request.server_port.filter(|port| {
+            !(self.inner.omit_default_port && default_port(&request.url_scheme) == Some(*port))
+        });
+        let network_protocol_name = (!self.inner.omit_network_protocol_name).then(|| {
+            match &self.inner.network_protocol_name {
+                Some(network_protocol_name) => network_protocol_name.clone(),
+                None => network_protocol_name(&request.url_scheme),
+            }
+        });
+
+        labels.push((
+            self.inner.label_names.http_request_method.clone(),
+            request.http_request_method,
+        ));
+        if !self.inner.omit_url_scheme {
+            labels.push((
+                self.inner.label_names.url_scheme.clone(),
+                request.url_scheme,
+            ));
+        }
+        if let Some(network_protocol_name) = network_protocol_name {
+            labels.push((
+                self.inner.label_names.network_protocol_name.clone(),
+                network_protocol_name,
+            ));
+        }
+
+        if let Some(server_address) = request.server_address {
+            labels.push((
+                self.inner.label_names.server_address.clone(),
+                Cow::Owned(server_address),
+            ));
+        }
+
+        if let Some(port) = server_port {
             labels.push((
-                self.label_names.server_port.to_string(),
+                self.inner.label_names.server_port.clone(),
                 Cow::Owned(port.to_string()),
             ));
         }
 
-        if let Some(network_protocol_version) = network_protocol_version {
+        if let Some(network_protocol_version) = request.network_protocol_version {
             labels.push((
-                self.label_names.network_protocol_version.to_string(),
+                self.inner.label_names.network_protocol_version.clone(),
                 Cow::Borrowed(network_protocol_version),
             ));
+
+            if self.inner.network_transport {
+                labels.push((
+                    self.inner.label_names.network_transport.clone(),
+                    Cow::Borrowed(network_transport(network_protocol_version)),
+                ));
+            }
+        }
+
+        if let Some(status) = status {
+            labels.push((
+                self.inner.label_names.http_response_status.clone(),
+                Cow::Owned(status.as_u16().to_string()),
+            ));
+
+            if self.inner.status_class {
+                if let Some(status_class) = http_response_status_class(status) {
+                    labels.push((
+                        self.inner.label_names.http_response_status_class.clone(),
+                        Cow::Borrowed(status_class),
+                    ));
+                }
+            }
+        }
+
+        if let Some(error) = error_type {
+            labels.push((self.inner.label_names.error_type.clone(), error));
+        }
+
+        if let Some(http_route) = request.http_route {
+            labels.push((
+                self.inner.label_names.http_route.clone(),
+                Cow::Owned(http_route),
+            ));
+        }
+
+        if let Some(url_full) = request.url_full {
+            labels.push((
+                self.inner.label_names.url_full.clone(),
+                Cow::Owned(url_full),
+            ));
+        }
+
+        if let Some(user_agent) = request.user_agent {
+            labels.push((
+                self.inner.label_names.user_agent_original.clone(),
+                Cow::Owned(user_agent),
+            ));
+        }
+
+        if let Some(peer_service) = &self.inner.peer_service {
+            labels.push((
+                self.inner.label_names.peer_service.clone(),
+                Cow::Owned(peer_service.clone()),
+            ));
+        }
+
+        if let Some(trace_id) = request.trace_id {
+            labels.push((
+                self.inner.label_names.trace_id.clone(),
+                Cow::Owned(trace_id),
+            ));
+        }
+
+        labels
+    }
+
+    /// Builds the labels that are read from the response headers.
+    fn response_labels(&self, headers: &HeaderMap) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        let mut labels = Vec::new();
+
+        if self.inner.response_content_type {
+            if let Some(content_type) = content_type(headers) {
+                labels.push((
+                    self.inner.label_names.http_response_content_type.clone(),
+                    Cow::Owned(content_type),
+                ));
+            }
+        }
+
+        labels
+    }
+
+    /// Sets the `network.protocol.name` of `101 Switching Protocols` responses to the protocol of
+    /// the `upgrade` header, if enabled.
+    fn upgrade_protocol_name(
+        &self,
+        labels: &mut [(Cow<'static, str>, Cow<'static, str>)],
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) {
+        if !self.inner.treat_upgrades_specially || status != StatusCode::SWITCHING_PROTOCOLS {
+            return;
+        }
+        let Some(protocol) = headers.get(UPGRADE).and_then(|value| value.to_str().ok()) else {
+            return;
+        };
+
+        // Drop the version of protocols like `TLS/1.2`
+        let protocol = protocol.split('/').next().unwrap_or(protocol).trim();
+        for (key, value) in labels {
+            if *key == self.inner.label_names.network_protocol_name {
+                *value = Cow::Owned(protocol.to_ascii_lowercase());
+            }
+        }
+    }
+
+    /// Builds the `connection.reused` label, if enabled and known.
+    fn connection_reused(
+        &self,
+        reused: Option<ConnectionReused>,
+    ) -> Option<(Cow<'static, str>, Cow<'static, str>)> {
+        let ConnectionReused(reused) = reused.filter(|_| self.inner.connection_reuse_label)?;
+        Some((
+            self.inner.label_names.connection_reused.clone(),
+            Cow::Borrowed(if reused { "true" } else { "false" }),
+        ))
+    }
+
+    /// Builds the `tls.protocol.version` label, if enabled and known.
+    fn tls_version(
+        &self,
+        version: Option<TlsVersion>,
+    ) -> Option<(Cow<'static, str>, Cow<'static, str>)> {
+        let TlsVersion(version) = version.filter(|_| self.inner.tls_version_label)?;
+        Some((
+            self.inner.label_names.tls_protocol_version.clone(),
+            Cow::Borrowed(version),
+        ))
+    }
+
+    /// Builds the labels that are read from the request [`Extensions`].
+    fn extension_labels(
+        &self,
+        extensions: &Extensions,
+    ) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        let mut labels = Vec::new();
+
+        let resend_count = match &self.inner.resend_count {
+            Some(resend_count) => (resend_count.0)(extensions),
+            None => extensions.get::<ResendCount>().map(|count| count.0),
+        };
+        if let Some(resend_count) = resend_count.filter(|count| *count > 0) {
+            labels.push((
+                self.inner.label_names.http_request_resend_count.clone(),
+                Cow::Owned(resend_count.to_string()),
+            ));
+        }
+
+        if let Some(RequestClass(request_class)) = extensions.get::<RequestClass>() {
+            labels.push((
+                self.inner.label_names.request_class.clone(),
+                Cow::Borrowed(*request_class),
+            ));
+        }
+
+        if let Some(cache_hit) = self
+            .inner
+            .cache_status
+            .as_ref()
+            .and_then(|cache_status| (cache_status.0)(extensions))
+        {
+            labels.push((
+                self.inner.label_names.cache_hit.clone(),
+                Cow::Borrowed(if cache_hit { "true" } else { "false" }),
+            ));
+        }
+
+        if let Some(operation) = self
+            .inner
+            .operation_name
+            .as_ref()
+            .and_then(|operation_name| (operation_name.0)(extensions))
+        {
+            labels.push((
+                self.inner.label_names.operation.clone(),
+                Cow::Owned(operation),
+            ));
+        }
+
+        labels
+    }
+
+    /// Returns the labels computed by [`MetricsMiddlewareBuilder::dynamic_labels`], if set.
+    fn dynamic_labels(&self, extensions: &Extensions) -> Vec<(String, String)> {
+        self.inner
+            .dynamic_labels
+            .as_ref()
+            .map(|dynamic_labels| (dynamic_labels.0)(extensions))
+            .unwrap_or_default()
+    }
+
+    /// Removes the labels of this crate that OTel does not define for a metric (`otel_labels`)
+    /// from `labels`, if [`MetricsMiddlewareBuilder::strict_otel_labels`] is enabled.
+    fn otel_labels<'a>(
+        &self,
+        labels: &'a [(Cow<'static, str>, Cow<'static, str>)],
+        otel_labels: &[&str],
+    ) -> Cow<'a, [(Cow<'static, str>, Cow<'static, str>)]> {
+        if !self.inner.strict_otel_labels {
+            return Cow::Borrowed(labels);
+        }
+
+        let excluded: Vec<_> = self
+            .inner
+            .label_names
+            .all()
+            .into_iter()
+            .filter(|(default, _)| !otel_labels.contains(default))
+            .map(|(_, name)| name)
+            .collect();
+        Cow::Owned(
+            labels
+                .iter()
+                .filter(|(key, _)| !excluded.contains(&key.as_ref()))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Returns a handle to record values to the histogram `name`, as configured by the
+    /// [`RecordingMode`].
+    fn histogram(
+        &self,
+        name: &str,
+        labels: &[(Cow<'static, str>, Cow<'static, str>)],
+    ) -> HistogramHandle {
+        match self.inner.recording_mode {
+            RecordingMode::Histogram => HistogramHandle::Histogram(
+                self.inner
+                    .sink
+                    .0
+                    .histogram(&Key::from_parts(name.to_string(), labels)),
+            ),
+            RecordingMode::GaugeSummary => HistogramHandle::GaugeSummary {
+                key: Key::from_parts(name.to_string(), labels),
+                sink: Arc::clone(&self.inner.sink.0),
+                clock: Arc::clone(&self.inner.clock.0),
+                interval: self.inner.gauge_summary_interval,
+            },
+        }
+    }
+
+    /// Returns the counter `name` from the [`MetricsSink`].
+    fn counter(&self, name: &str, labels: &[(Cow<'static, str>, Cow<'static, str>)]) -> Counter {
+        self.inner
+            .sink
+            .0
+            .counter(&Key::from_parts(name.to_string(), labels))
+    }
+
+    /// Returns the gauge `name` from the [`MetricsSink`].
+    fn gauge(&self, name: &str, labels: &[(Cow<'static, str>, Cow<'static, str>)]) -> Gauge {
+        self.inner
+            .sink
+            .0
+            .gauge(&Key::from_parts(name.to_string(), labels))
+    }
+
+    /// Records the `http.client.request.duration` (or `CONNECT` and upgrade equivalent) of a
+    /// completed request.
+    fn record_duration(
+        &self,
+        labels: &[(Cow<'static, str>, Cow<'static, str>)],
+        duration: Duration,
+    ) {
+        let is_connect = || {
+            labels.iter().any(|(key, value)| {
+                *key == self.inner.label_names.http_request_method && value == "CONNECT"
+            })
+        };
+        let is_upgrade = || {
+            labels.iter().any(|(key, value)| {
+                *key == self.inner.label_names.http_response_status
+                    && value == StatusCode::SWITCHING_PROTOCOLS.as_str()
+            })
+        };
+        let request_duration = if self.inner.separate_connect_metrics && is_connect() {
+            &self.inner.metric_names.connect_duration
+        } else if self.inner.treat_upgrades_specially && is_upgrade() {
+            &self.inner.metric_names.upgrade_duration
+        } else {
+            &self.inner.metric_names.request_duration
+        };
+        self.histogram(
+            request_duration,
+            &self.otel_labels(labels, &OTEL_REQUEST_LABELS),
+        )
+        .record(self.inner.duration_unit.value(duration));
+    }
+
+    /// Records the duration and request metrics of a completed request.
+    fn record_request(
+        &self,
+        labels: &[(Cow<'static, str>, Cow<'static, str>)],
+        duration: Duration,
+        request_body_size: Option<u64>,
+        connection_duration: Option<ConnectionDuration>,
+        dns_duration: Option<DnsDuration>,
+    ) {
+        self.record_duration(labels, duration);
+
+        if self.inner.total_duration_counter {
+            self.counter(&self.inner.metric_names.request_duration_total, labels)
+                .increment(duration.as_micros() as u64);
+        }
+
+        if self
+            .inner
+            .slow_request_threshold
+            .is_some_and(|threshold| duration > threshold)
+        {
+            let slow_labels: Vec<_> = labels
+                .iter()
+                .filter(|(key, _)| {
+                    *key == self.inner.label_names.http_request_method
+                        || *key == self.inner.label_names.server_address
+                        || *key == self.inner.label_names.http_client_name
+                })
+                .cloned()
+                .collect();
+            self.counter(&self.inner.metric_names.slow_request_count, &slow_labels)
+                .increment(1);
+        }
+
+        if self.inner.connection_duration {
+            if let Some(ConnectionDuration(connection_duration)) = connection_duration {
+                self.histogram(
+                    &self.inner.metric_names.connection_duration,
+                    &self.otel_labels(labels, &OTEL_CONNECTION_DURATION_LABELS),
+                )
+                .record(self.inner.duration_unit.value(connection_duration));
+            }
+        }
+
+        if self.inner.last_duration_gauge {
+            let server_address: Vec<_> = labels
+                .iter()
+                .filter(|(key, _)| {
+                    *key == self.inner.label_names.server_address
+                        || *key == self.inner.label_names.http_client_name
+                })
+                .cloned()
+                .collect();
+            self.gauge(
+                &self.inner.metric_names.request_last_duration,
+                &server_address,
+            )
+            .set(self.inner.duration_unit.value(duration));
         }
 
-        if let Some(status) = http_response_status(&res) {
-            labels.push((self.label_names.http_response_status.to_string(), status));
+        if self.inner.dns_duration {
+            if let Some(DnsDuration(dns_duration)) = dns_duration {
+                self.histogram(&self.inner.metric_names.dns_duration, labels)
+                    .record(self.inner.duration_unit.value(dns_duration));
+            }
         }
 
-        if let Some(error) = error_type(&res) {
-            labels.push((self.label_names.error_type.to_string(), error));
+        if self.inner.request_body_size {
+            if let Some(request_body_size) = request_body_size {
+                self.histogram(
+                    &self.inner.metric_names.request_body_size,
+                    &self.otel_labels(labels, &OTEL_REQUEST_LABELS),
+                )
+                .record(self.inner.body_size_unit.value(request_body_size));
+            }
         }
 
-        histogram!(HTTP_CLIENT_REQUEST_DURATION, &labels)
-            .record(duration.as_millis() as f64 / 1000.0);
+        #[cfg(feature = "tracing")]
+        if let Some(level) = self.inner.tracing_events {
+            emit_tracing_event(level, labels, duration);
+        }
+    }
 
-        histogram!(HTTP_CLIENT_REQUEST_BODY_SIZE, &labels).record(request_body_size as f64);
+    /// Records the response body size, returning the (possibly wrapped) response.
+    fn record_response_body_size(
+        &self,
+        res: Result<Response>,
+        histogram: HistogramHandle,
+    ) -> Result<Response> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.inner.measure_response_body {
+            if let Ok(res) = res {
+                return Ok(body::measure_response_body(
+                    res,
+                    histogram,
+                    self.inner.body_size_unit,
+                ));
+            }
+        }
 
         // NOTE: The response body size is not *guaranteed* to be in the content-length header, but
         //       it will be added in nearly all modern HTTP implementations and waiting on the
         //       response body would be a fairly large performance pentality to force on our users.
-        let response_body_size = res
-            .as_ref()
-            .ok()
-            .and_then(|res| res.content_length())
-            .unwrap_or(0);
-        histogram!(HTTP_CLIENT_RESPONSE_BODY_SIZE, &labels).record(response_body_size as f64);
-
+        let response_body_size = res.as_ref().ok().and_then(response_body_size).unwrap_or(0);
+        histogram.record(self.inner.body_size_unit.value(response_body_size));
         res
     }
 }
 
-fn http_request_method(req: &Request) -> Cow<'static, str> {
-    match req.method() {
-        &Method::GET => Cow::Borrowed("GET"),
-        &Method::POST => Cow::Borrowed("POST"),
-        &Method::PUT => Cow::Borrowed("PUT"),
-        &Method::DELETE => Cow::Borrowed("DELETE"),
-        &Method::HEAD => Cow::Borrowed("HEAD"),
-        &Method::OPTIONS => Cow::Borrowed("OPTIONS"),
-        &Method::CONNECT => Cow::Borrowed("CONNECT"),
-        &Method::PATCH => Cow::Borrowed("PATCH"),
-        &Method::TRACE => Cow::Borrowed("TRACE"),
-        method => Cow::Owned(method.as_str().to_string()),
+/// Returns the [`Extensions`] of a response.
+#[cfg(not(target_arch = "wasm32"))]
+fn response_extensions(res: &Response) -> Option<&Extensions> {
+    Some(res.extensions())
+}
+
+/// Returns no [`Extensions`], since wasm responses have none.
+#[cfg(target_arch = "wasm32")]
+fn response_extensions(_res: &Response) -> Option<&Extensions> {
+    None
+}
+
+/// Converts labels to the owned keys of the public API, since labels are recorded with borrowed
+/// keys when the default label names are used.
+fn owned_keys(
+    labels: &[(Cow<'static, str>, Cow<'static, str>)],
+) -> Vec<(String, Cow<'static, str>)> {
+    labels
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect()
+}
+
+/// Appends the custom labels, which replace the value of labels that were already recorded.
+fn push_custom_labels(
+    labels: &mut Vec<(Cow<'static, str>, Cow<'static, str>)>,
+    custom: impl IntoIterator<Item = (String, String)>,
+) {
+    for (key, value) in custom {
+        match labels.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, existing)) => *existing = Cow::Owned(value),
+            None => labels.push((Cow::Owned(key), Cow::Owned(value))),
+        }
+    }
+}
+
+fn known_http_request_method(method: &Method) -> Option<&'static str> {
+    Some(match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::PATCH => "PATCH",
+        Method::TRACE => "TRACE",
+        _ => return None,
+    })
+}
+
+/// Returns the size of the request body, falling back to the `content-length` header for
+/// streaming bodies. Returns `None` when the size cannot be determined.
+#[cfg(not(target_arch = "wasm32"))]
+fn request_body_size(req: &Request) -> Option<u64> {
+    let Some(body) = req.body() else {
+        return Some(0);
+    };
+    if let Some(bytes) = body.as_bytes() {
+        return Some(bytes.len() as u64);
+    }
+    content_length(req.headers())
+}
+
+/// Returns the size of the request body. Returns `None` for multipart forms, whose size is only
+/// known to the browser.
+///
+/// Unlike on native targets, the `content-length` header is not used as a fallback since it is a
+/// forbidden header for the fetch API, which computes it itself.
+#[cfg(target_arch = "wasm32")]
+fn request_body_size(req: &Request) -> Option<u64> {
+    match req.body() {
+        Some(body) => body.as_bytes().map(|bytes| bytes.len() as u64),
+        None => Some(0),
+    }
+}
+
+/// Returns the size of the response body from its size hint, which is usually the
+/// `content-length` header.
+#[cfg(not(target_arch = "wasm32"))]
+fn response_body_size(res: &Response) -> Option<u64> {
+    res.content_length()
+}
+
+/// Returns the size of the response body from the `content-length` header, since fetch responses
+/// have no size hint. The header is CORS-safelisted, so it is available for cross-origin
+/// responses too.
+#[cfg(target_arch = "wasm32")]
+fn response_body_size(res: &Response) -> Option<u64> {
+    content_length(res.headers())
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+/// Emits the `tracing` event of a completed request.
+#[cfg(feature = "tracing")]
+fn emit_tracing_event(
+    level: tracing::Level,
+    labels: &[(Cow<'static, str>, Cow<'static, str>)],
+    duration: Duration,
+) {
+    use tracing::Level;
+
+    // `tracing` requires the level of an event to be a constant
+    macro_rules! event {
+        ($level:expr) => {
+            tracing::event!(
+                $level,
+                labels = %DisplayLabels(labels),
+                ?duration,
+                "HTTP client request completed"
+            )
+        };
+    }
+
+    match level {
+        Level::ERROR => event!(Level::ERROR),
+        Level::WARN => event!(Level::WARN),
+        Level::INFO => event!(Level::INFO),
+        Level::DEBUG => event!(Level::DEBUG),
+        _ => event!(Level::TRACE),
+    }
+}
+
+/// Formats labels as space separated `key=value` pairs.
+#[cfg(feature = "tracing")]
+struct DisplayLabels<'a>(&'a [(Cow<'static, str>, Cow<'static, str>)]);
+
+#[cfg(feature = "tracing")]
+impl fmt::Display for DisplayLabels<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{key}={value}")?;
+        }
+        Ok(())
     }
 }
 
-fn url_scheme(req: &Request) -> Cow<'static, str> {
-    match req.url().scheme() {
+/// Returns the trace ID of the current `tracing` span, falling back to the current OpenTelemetry
+/// context for applications that do not use `tracing-opentelemetry`.
+#[cfg(feature = "otel-trace-id")]
+fn current_trace_id() -> Option<String> {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    [
+        tracing::Span::current().context(),
+        opentelemetry::Context::current(),
+    ]
+    .into_iter()
+    .map(|context| context.span().span_context().clone())
+    .find(|span_context| span_context.is_valid())
+    .map(|span_context| span_context.trace_id().to_string())
+}
+
+/// Returns the media type of the `content-type` header, without any parameters.
+fn content_type(headers: &HeaderMap) -> Option<String> {
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let media_type = content_type.split(';').next()?.trim();
+    (!media_type.is_empty()).then(|| media_type.to_ascii_lowercase())
+}
+
+fn url_scheme(scheme: &str) -> Cow<'static, str> {
+    match scheme {
         "http" => Cow::Borrowed("http"),
         "https" => Cow::Borrowed("https"),
         s => Cow::Owned(s.to_string()),
     }
 }
 
+/// Returns a random number in `[0, 1)` from a thread-local xorshift generator, which is plenty
+/// for sampling and avoids a dependency on `rand`.
+fn random() -> f64 {
+    thread_local! {
+        static STATE: Cell<u64> = Cell::new(
+            // Seeded from the random keys of `RandomState`, forced odd to never be zero
+            RandomState::new().build_hasher().finish() | 1,
+        );
+    }
+
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+/// Returns the default `network.protocol.name` for a URL scheme.
+fn network_protocol_name(scheme: &str) -> Cow<'static, str> {
+    match scheme {
+        "ws" | "wss" => Cow::Borrowed("websocket"),
+        _ => Cow::Borrowed("http"),
+    }
+}
+
+/// Removes the parts of `url` that commonly contain secrets: the query, fragment and credentials.
+fn redact_url(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    // These only fail for URLs that cannot have credentials in the first place
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url.into()
+}
+
 fn server_address(req: &Request) -> Option<String> {
-    req.url().host().map(|h| h.to_string())
+    req.url()
+        .host_str()
+        .map(|host| unbracket_host(host).to_string())
+}
+
+/// Lowercases hostnames (ASCII only), which are case-insensitive, so `Example.com` and
+/// `example.com` are recorded as the same `server.address`. IP literals are kept unchanged.
+///
+/// Only needed for headers and `http::Uri` hosts, since `Url` already lowercases hosts.
+fn lowercase_host(host: &str) -> String {
+    if host.parse::<IpAddr>().is_ok() {
+        host.to_string()
+    } else {
+        host.to_ascii_lowercase()
+    }
+}
+
+/// Strips the brackets around IPv6 literals (ie. `[::1]`), which are part of the URL syntax rather
+/// than the address, as expected by Open Telemetry.
+fn unbracket_host(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
 }
 
 fn server_port(req: &Request) -> Option<u16> {
     req.url().port_or_known_default()
 }
 
-fn http_response_status(res: &Result<Response>) -> Option<Cow<'static, str>> {
-    res.as_ref()
-        .map(|r| Cow::Owned(r.status().as_u16().to_string()))
-        .ok()
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" => Some(80),
+        "https" => Some(443),
+        _ => None,
+    }
 }
 
-fn error_type(res: &Result<Response>) -> Option<Cow<'static, str>> {
-    Some(match res {
-        Ok(res) if res.status().is_client_error() || res.status().is_server_error() => {
-            Cow::Owned(res.status().as_str().to_string())
-        }
-        Err(Error::Middleware(err)) => Cow::Owned(format!("{err}")),
-        Err(Error::Reqwest(err)) => Cow::Owned(format!("{err}")),
+fn network_transport(network_protocol_version: &str) -> &'static str {
+    match network_protocol_version {
+        "3" => "quic",
+        _ => "tcp",
+    }
+}
+
+fn http_response_status_class(status: StatusCode) -> Option<&'static str> {
+    Some(match status.as_u16() / 100 {
+        1 => "1xx",
+        2 => "2xx",
+        3 => "3xx",
+        4 => "4xx",
+        5 => "5xx",
         _ => return None,
     })
 }
 
+/// Returns the status of the response, which is also available for some errors (ie. when a
+/// downstream middleware used [`Response::error_for_status`]).
+fn response_status(res: &Result<Response>) -> Option<StatusCode> {
+    match res {
+        Ok(res) => Some(res.status()),
+        Err(Error::Reqwest(err)) => err.status(),
+        Err(Error::Middleware(_)) => None,
+    }
+}
+
+/// Maps a [`reqwest::Error`](reqwest_middleware::reqwest::Error) to a low cardinality
+/// `error.type`, as recommended by Open Telemetry.
+fn reqwest_error_type(err: &reqwest_middleware::reqwest::Error) -> &'static str {
+    if err.is_timeout() {
+        return "timeout";
+    }
+    // Connection errors are also request errors, so this must be checked first
+    #[cfg(not(target_arch = "wasm32"))]
+    if err.is_connect() {
+        return "connect";
+    }
+    if err.is_request() {
+        "request"
+    } else if err.is_decode() {
+        "decode"
+    } else {
+        "_OTHER"
+    }
+}
+
+// The fetch API does not expose the negotiated protocol (and reqwest does not expose the version
+// of wasm responses), so the label is omitted rather than guessed.
 #[cfg(target_arch = "wasm32")]
 fn network_protocol_version(_req: &Request) -> Option<&'static str> {
     None
@@ -356,8 +4314,11 @@ fn network_protocol_version(_req: &Request) -> Option<&'static str> {
 
 #[cfg(not(target_arch = "wasm32"))]
 fn network_protocol_version(req: &Request) -> Option<&'static str> {
-    let version = req.version();
+    http_version(req.version())
+}
 
+#[cfg(any(not(target_arch = "wasm32"), feature = "tower"))]
+fn http_version(version: http::Version) -> Option<&'static str> {
     Some(match version {
         http::Version::HTTP_09 => "0.9",
         http::Version::HTTP_10 => "1.0",
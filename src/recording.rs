@@ -0,0 +1,251 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+use metrics::{describe_counter, describe_gauge, describe_histogram, Histogram, Key, Unit};
+
+use crate::{Clock, MetricsSink};
+
+/// How the histogram metrics (ie. `http.client.request.duration`) are recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordingMode {
+    /// Record values to histograms.
+    #[default]
+    Histogram,
+    /// Record a `<name>.min`, `<name>.max` and `<name>.count` gauge for every histogram instead,
+    /// for backends that do not support histograms.
+    ///
+    /// The gauges summarize the values recorded to the current recorder by every
+    /// [`MetricsMiddleware`] with the same metric and labels over the last
+    /// [`gauge_summary_interval`](crate::MetricsMiddlewareBuilder::gauge_summary_interval).
+    ///
+    /// [`MetricsMiddleware`]: crate::MetricsMiddleware
+    GaugeSummary,
+}
+
+impl RecordingMode {
+    /// Describes a histogram metric, or its summary gauges.
+    pub(crate) fn describe(self, name: &str, unit: Unit, description: &str) {
+        let kind = match self {
+            RecordingMode::Histogram => MetricKind::Histogram,
+            RecordingMode::GaugeSummary => MetricKind::GaugeSummary,
+        };
+        describe_once(kind, name, unit, description);
+    }
+}
+
+/// The kinds of metrics described with [`describe_once`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+    /// The `<name>.min`, `<name>.max` and `<name>.count` gauges of a histogram recorded in
+    /// [`RecordingMode::GaugeSummary`].
+    GaugeSummary,
+}
+
+/// The metrics described to the last recorder, identified by its address.
+#[derive(Default)]
+struct Described {
+    recorder: usize,
+    metrics: HashMap<String, Vec<(MetricKind, Unit, String)>>,
+}
+
+impl Described {
+    fn contains(
+        &self,
+        recorder: usize,
+        kind: MetricKind,
+        name: &str,
+        unit: Unit,
+        description: &str,
+    ) -> bool {
+        self.recorder == recorder
+            && self.metrics.get(name).is_some_and(|described| {
+                described
+                    .iter()
+                    .any(|(described_kind, described_unit, described_description)| {
+                        *described_kind == kind
+                            && *described_unit == unit
+                            && described_description == description
+                    })
+            })
+    }
+}
+
+/// Describes the metric `name` to the current recorder, unless it was already described to it
+/// with the same kind, unit and description.
+///
+/// Middlewares are often built per client (or even per request), so this avoids repeating the
+/// work for every construction: metrics that were already described are looked up under a read
+/// lock, without allocating. Only the metrics described to the last recorder are remembered, so
+/// switching recorders (ie. to a local recorder) describes them again and the memory used is
+/// bounded by the metrics of a single recorder.
+///
+/// Recorders are told apart by their address, so a recorder created at the address of a dropped
+/// one (ie. a local recorder created in a loop) is not described the metrics the dropped one was,
+/// unless the dropped one was passed to [`forget_recorder`].
+pub(crate) fn describe_once(kind: MetricKind, name: &str, unit: Unit, description: &str) {
+    let recorder = metrics::with_recorder(recorder_address);
+    let described = described();
+    if described
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .contains(recorder, kind, name, unit, description)
+    {
+        return;
+    }
+
+    {
+        let mut described = described.write().unwrap_or_else(PoisonError::into_inner);
+        if described.contains(recorder, kind, name, unit, description) {
+            return;
+        }
+        if described.recorder != recorder {
+            *described = Described {
+                recorder,
+                metrics: HashMap::new(),
+            };
+        }
+        described
+            .metrics
+            .entry(name.to_string())
+            .or_default()
+            .push((kind, unit, description.to_string()));
+    }
+
+    let description = description.to_string();
+    match kind {
+        MetricKind::Counter => describe_counter!(name.to_string(), unit, description),
+        MetricKind::Gauge => describe_gauge!(name.to_string(), unit, description),
+        MetricKind::Histogram => describe_histogram!(name.to_string(), unit, description),
+        MetricKind::GaugeSummary => {
+            for (suffix, unit) in [("min", unit), ("max", unit), ("count", Unit::Count)] {
+                describe_gauge!(format!("{name}.{suffix}"), unit, description.clone());
+            }
+        }
+    }
+}
+
+/// Forgets the metrics described and summarized to `recorder` before it is dropped, so a recorder
+/// later created at its address starts afresh.
+#[cfg(feature = "test-util")]
+pub(crate) fn forget_recorder(recorder: &dyn metrics::Recorder) {
+    let recorder = recorder_address(recorder);
+    {
+        let mut described = described().write().unwrap_or_else(PoisonError::into_inner);
+        if described.recorder == recorder {
+            *described = Described::default();
+        }
+    }
+    gauge_summaries()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&recorder);
+}
+
+fn described() -> &'static RwLock<Described> {
+    static DESCRIBED: OnceLock<RwLock<Described>> = OnceLock::new();
+    DESCRIBED.get_or_init(Default::default)
+}
+
+fn recorder_address(recorder: &dyn metrics::Recorder) -> usize {
+    (recorder as *const dyn metrics::Recorder).cast::<()>() as usize
+}
+
+/// The summaries of the values recorded in [`RecordingMode::GaugeSummary`] by recorder address,
+/// shared by every middleware so that middlewares recording the same metric and labels to the
+/// same recorder report a single summary.
+///
+/// Summaries whose interval has elapsed are removed when a new metric or label set is summarized,
+/// so only the ones recorded within the last interval are kept.
+fn gauge_summaries() -> &'static Mutex<HashMap<usize, HashMap<Key, Summary>>> {
+    static SUMMARIES: OnceLock<Mutex<HashMap<usize, HashMap<Key, Summary>>>> = OnceLock::new();
+    SUMMARIES.get_or_init(Default::default)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Summary {
+    min: f64,
+    max: f64,
+    count: u64,
+    started: Instant,
+}
+
+impl Summary {
+    fn new(started: Instant) -> Self {
+        Self {
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            count: 0,
+            started,
+        }
+    }
+}
+
+/// A handle to record values to a histogram metric according to the [`RecordingMode`].
+pub(crate) enum HistogramHandle {
+    Histogram(Histogram),
+    GaugeSummary {
+        key: Key,
+        sink: Arc<dyn MetricsSink>,
+        clock: Arc<dyn Clock>,
+        interval: Duration,
+    },
+}
+
+impl HistogramHandle {
+    pub(crate) fn record(&self, value: f64) {
+        match self {
+            HistogramHandle::Histogram(histogram) => histogram.record(value),
+            HistogramHandle::GaugeSummary {
+                key,
+                sink,
+                clock,
+                interval,
+            } => {
+                let now = clock.now();
+                let expired = |summary: &Summary| now.duration_since(summary.started) >= *interval;
+                let recorder = metrics::with_recorder(recorder_address);
+                let summary = {
+                    let mut summaries = gauge_summaries()
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    if !summaries
+                        .get(&recorder)
+                        .is_some_and(|summaries| summaries.contains_key(key))
+                    {
+                        summaries.retain(|_, summaries| {
+                            summaries.retain(|_, summary| !expired(summary));
+                            !summaries.is_empty()
+                        });
+                    }
+                    let summary = summaries
+                        .entry(recorder)
+                        .or_default()
+                        .entry(key.clone())
+                        .or_insert_with(|| Summary::new(now));
+                    if expired(summary) {
+                        *summary = Summary::new(now);
+                    }
+                    summary.min = summary.min.min(value);
+                    summary.max = summary.max.max(value);
+                    summary.count += 1;
+                    *summary
+                };
+
+                let name = key.name();
+                let labels: Vec<_> = key.labels().cloned().collect();
+                let gauge = |suffix| {
+                    sink.gauge(&Key::from_parts(format!("{name}.{suffix}"), labels.clone()))
+                };
+                gauge("min").set(summary.min);
+                gauge("max").set(summary.max);
+                gauge("count").set(summary.count as f64);
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Helpers to test the metrics recorded by [`MetricsMiddleware`](crate::MetricsMiddleware),
+//! available with the `test-util` feature.
+//!
+//! ```
+//! use reqwest_metrics::test_util::install_debug_recorder;
+//!
+//! let (snapshotter, _guard) = install_debug_recorder();
+//! // Send requests through the middleware on this thread...
+//! assert!(snapshotter.snapshot().into_vec().is_empty());
+//! ```
+
+use std::{mem::ManuallyDrop, ptr::NonNull};
+
+use metrics::LocalRecorderGuard;
+use metrics_util::debugging::{DebuggingRecorder, Snapshotter};
+
+/// [`insta`](https://docs.rs/insta) filters replacing the values that change between runs (ie.
+/// histogram values and the `server.port` of the mock server) in `Debug` snapshots of the
+/// recorded metrics.
+pub const SNAPSHOT_FILTERS: [(&str, &str); 3] = [
+    (
+        r"Histogram\(\s*[\s\S]*?\s*\)",
+        "Histogram([HISTOGRAM_VALUE])",
+    ),
+    (
+        r#"Label\(\s*"server.port"\s*,\s*[\s\S]*?\s*\)"#,
+        r#"Label("server.port", [PORT])"#,
+    ),
+    (r#"hash: \d*"#, "hash: [HASH]"),
+];
+
+/// Installs a [`DebuggingRecorder`] as the thread-local recorder, so tests running in parallel
+/// don't fight over the global recorder.
+///
+/// Metrics are recorded until the returned guard is dropped, which also frees the recorder. With a
+/// multi-threaded runtime, only requests sent from the current thread are recorded.
+pub fn install_debug_recorder() -> (Snapshotter, DebugRecorderGuard) {
+    let recorder = NonNull::from(Box::leak(Box::new(DebuggingRecorder::new())));
+    // SAFETY: the recorder is only freed in `Drop`, after the local guard borrowing it.
+    let recorder_ref: &'static DebuggingRecorder = unsafe { recorder.as_ref() };
+    let snapshotter = recorder_ref.snapshotter();
+    let guard = metrics::set_default_local_recorder(recorder_ref);
+    (
+        snapshotter,
+        DebugRecorderGuard {
+            guard: ManuallyDrop::new(guard),
+            recorder,
+        },
+    )
+}
+
+/// Keeps the recorder installed by [`install_debug_recorder`] alive, and restores the previous
+/// thread-local recorder when dropped.
+#[must_use = "the recorder is uninstalled when the guard is dropped"]
+pub struct DebugRecorderGuard {
+    guard: ManuallyDrop<LocalRecorderGuard<'static>>,
+    recorder: NonNull<DebuggingRecorder>,
+}
+
+impl Drop for DebugRecorderGuard {
+    fn drop(&mut self) {
+        // SAFETY: the local guard is dropped exactly once, before the recorder it borrows, and
+        // the recorder was allocated by the `Box` leaked in `install_debug_recorder`.
+        unsafe {
+            ManuallyDrop::drop(&mut self.guard);
+            let recorder = Box::from_raw(self.recorder.as_ptr());
+            // The next recorder may be allocated at the same address
+            crate::recording::forget_recorder(&*recorder);
+        }
+    }
+}
+
+impl std::fmt::Debug for DebugRecorderGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DebugRecorderGuard").finish_non_exhaustive()
+    }
+}
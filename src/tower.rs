@@ -0,0 +1,320 @@
+use std::{
+    borrow::Cow,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+    time::Instant,
+};
+
+use http::{Extensions, Uri};
+use http_body::Body;
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    content_length, default_port, http_version, lowercase_host, unbracket_host, url_scheme,
+    InFlight, MetricsMiddleware, Outcome, RequestLabels,
+};
+
+/// [`Layer`] that wraps services with a [`MetricsService`] to emit the same metrics as
+/// [`MetricsMiddleware`] for any `tower` based HTTP client.
+///
+/// The layer is configured with a [`MetricsMiddleware`] and records requests the same way, so
+/// label and metric names are shared. Options that need reqwest types are ignored:
+///
+/// - [`skip_if`], [`labels_from_request`], [`url_template_fn`] and [`request_size_hint`], which
+///   take a reqwest request or URL.
+/// - [`enable_uri`], since the URL of a `tower` request is not necessarily absolute.
+/// - [`error_type_fn`] and [`detailed_error_type`], since `tower` errors are opaque: the
+///   `error.type` of failed requests is always `_OTHER`.
+/// - [`on_complete`], which takes a reqwest response.
+/// - [`enable_timeout_counter`] and [`enable_redirect_count`].
+/// - [`measure_response_body`], since the response body size is read from the `content-length`
+///   header or the size hint of the body.
+///
+/// [`skip_if`]: crate::MetricsMiddlewareBuilder::skip_if
+/// [`labels_from_request`]: crate::MetricsMiddlewareBuilder::labels_from_request
+/// [`url_template_fn`]: crate::MetricsMiddlewareBuilder::url_template_fn
+/// [`request_size_hint`]: crate::MetricsMiddlewareBuilder::request_size_hint
+/// [`enable_uri`]: crate::MetricsMiddlewareBuilder::enable_uri
+/// [`error_type_fn`]: crate::MetricsMiddlewareBuilder::error_type_fn
+/// [`detailed_error_type`]: crate::MetricsMiddlewareBuilder::detailed_error_type
+/// [`on_complete`]: crate::MetricsMiddlewareBuilder::on_complete
+/// [`enable_timeout_counter`]: crate::MetricsMiddlewareBuilder::enable_timeout_counter
+/// [`enable_redirect_count`]: crate::MetricsMiddlewareBuilder::enable_redirect_count
+/// [`measure_response_body`]: crate::MetricsMiddlewareBuilder::measure_response_body
+#[derive(Debug, Clone)]
+pub struct MetricsLayer {
+    metrics: Arc<MetricsMiddleware>,
+}
+
+impl MetricsLayer {
+    /// Create a new [`MetricsLayer`] recording metrics as configured on `metrics`.
+    pub fn new(metrics: MetricsMiddleware) -> Self {
+        Self {
+            metrics: Arc::new(metrics),
+        }
+    }
+}
+
+impl Default for MetricsLayer {
+    fn default() -> Self {
+        Self::new(MetricsMiddleware::new())
+    }
+}
+
+impl From<MetricsMiddleware> for MetricsLayer {
+    fn from(metrics: MetricsMiddleware) -> Self {
+        Self::new(metrics)
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            inner,
+            metrics: Arc::clone(&self.metrics),
+        }
+    }
+}
+
+/// [`Service`] that emits HTTP client metrics for the requests sent through it.
+///
+/// See [`MetricsLayer`] for more details.
+#[derive(Debug, Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+    metrics: Arc<MetricsMiddleware>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for MetricsService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    ReqBody: Body,
+    ResBody: Body,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        if cfg!(feature = "disabled") {
+            return ResponseFuture {
+                inner: self.inner.call(req),
+                state: None,
+            };
+        }
+
+        let metrics = &self.metrics;
+        if !metrics.sampled() {
+            let state = metrics
+                .inner
+                .record_duration_always
+                .then(|| State::DurationOnly {
+                    metrics: Arc::clone(metrics),
+                    request_labels: request_labels(metrics, &req),
+                    extensions: req.extensions().clone(),
+                    start: metrics.inner.clock.0.now(),
+                });
+            return ResponseFuture {
+                inner: self.inner.call(req),
+                state,
+            };
+        }
+
+        let request_labels = request_labels(metrics, &req);
+        let request_body_size = req
+            .body()
+            .size_hint()
+            .exact()
+            .or_else(|| content_length(req.headers()))
+            .or_else(|| (!metrics.inner.skip_unknown_request_body_size).then_some(0));
+        // The request is moved into the inner service, so its extensions are cloned to be read
+        // once the request completes, like the middleware does.
+        let extensions = req.extensions().clone();
+        let head = req.method() == http::Method::HEAD;
+        let request_header_count = req.headers().len();
+
+        let in_flight = InFlight::new(Arc::clone(metrics), request_labels, request_body_size);
+        ResponseFuture {
+            inner: self.inner.call(req),
+            state: Some(State::Sampled {
+                in_flight,
+                extensions,
+                head,
+                request_header_count,
+            }),
+        }
+    }
+}
+
+pin_project! {
+    /// Response future for [`MetricsService`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        state: Option<State>,
+    }
+}
+
+enum State {
+    /// A sampled request, recording every metric.
+    Sampled {
+        in_flight: InFlight<Arc<MetricsMiddleware>>,
+        extensions: Extensions,
+        head: bool,
+        request_header_count: usize,
+    },
+    /// A request that was not sampled, only recording its duration (see
+    /// [`MetricsMiddlewareBuilder::record_duration_always`]).
+    ///
+    /// [`MetricsMiddlewareBuilder::record_duration_always`]: crate::MetricsMiddlewareBuilder::record_duration_always
+    DurationOnly {
+        metrics: Arc<MetricsMiddleware>,
+        request_labels: RequestLabels,
+        extensions: Extensions,
+        start: Instant,
+    },
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<http::Response<ResBody>, E>>,
+    ResBody: Body,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let res = match panic::catch_unwind(AssertUnwindSafe(|| this.inner.as_mut().poll(cx))) {
+            Ok(poll) => ready!(poll),
+            Err(panic) => {
+                if let Some(State::Sampled { in_flight, .. }) = this.state {
+                    in_flight.abort("panic");
+                }
+                panic::resume_unwind(panic)
+            }
+        };
+
+        if let Some(state) = this.state.take() {
+            state.record(&res);
+        }
+
+        Poll::Ready(res)
+    }
+}
+
+impl State {
+    fn record<ResBody: Body, E>(self, res: &Result<http::Response<ResBody>, E>) {
+        match self {
+            State::Sampled {
+                mut in_flight,
+                extensions,
+                head,
+                request_header_count,
+            } => {
+                let (request_labels, duration) = in_flight.finish();
+                let metrics = &in_flight.metrics;
+                let outcome = outcome(metrics, res, &extensions);
+                let labels = metrics.record_completed(
+                    request_labels,
+                    in_flight.request_body_size,
+                    request_header_count,
+                    &outcome,
+                    duration,
+                );
+                metrics.record_known_response_body_size(
+                    &labels,
+                    head,
+                    res.as_ref().ok().and_then(|res| {
+                        content_length(res.headers()).or_else(|| res.body().size_hint().exact())
+                    }),
+                );
+                metrics.record_counters(&labels, outcome.failed, outcome.redirected);
+            }
+            State::DurationOnly {
+                metrics,
+                request_labels,
+                extensions,
+                start,
+            } => {
+                let duration = metrics.inner.clock.0.now().saturating_duration_since(start);
+                let labels =
+                    metrics.completed_labels(request_labels, &outcome(&metrics, res, &extensions));
+                metrics.record_duration(&labels, duration);
+            }
+        }
+    }
+}
+
+/// Builds the labels known before a request is sent.
+fn request_labels<B>(metrics: &MetricsMiddleware, req: &http::Request<B>) -> RequestLabels {
+    RequestLabels {
+        http_request_method: metrics.http_request_method(req.method()),
+        url_scheme: url_scheme(req.uri().scheme_str().unwrap_or("http")),
+        server_address: metrics.bounded_server_address(
+            metrics
+                .server_address_from_header(req.headers())
+                .or_else(|| {
+                    req.uri()
+                        .host()
+                        .map(|host| lowercase_host(unbracket_host(host)))
+                }),
+            true,
+        ),
+        server_port: metrics
+            .server_port_from_header(req.headers())
+            .or_else(|| server_port(req.uri())),
+        network_protocol_version: http_version(req.version()),
+        http_route: None,
+        url_full: None,
+        user_agent: metrics.user_agent(req.headers()),
+        trace_id: metrics.trace_id(),
+        custom: Vec::new(),
+    }
+}
+
+/// Describes a completed request. Errors of the inner service are opaque, so their `error.type`
+/// is always `_OTHER`.
+fn outcome<'a, B, E>(
+    metrics: &MetricsMiddleware,
+    res: &'a Result<http::Response<B>, E>,
+    extensions: &'a Extensions,
+) -> Outcome<'a> {
+    match res {
+        Ok(res) => {
+            let error_type = metrics.status_error_type(res.status());
+            Outcome {
+                status: Some(res.status()),
+                response: Some((res.headers(), Some(res.extensions()))),
+                extensions,
+                failed: error_type.is_some(),
+                error_type,
+                timed_out: false,
+                redirected: false,
+            }
+        }
+        Err(_) => Outcome {
+            status: None,
+            response: None,
+            extensions,
+            error_type: Some(Cow::Borrowed("_OTHER")),
+            failed: true,
+            timed_out: false,
+            redirected: false,
+        },
+    }
+}
+
+fn server_port(uri: &Uri) -> Option<u16> {
+    uri.port_u16().or_else(|| default_port(uri.scheme_str()?))
+}
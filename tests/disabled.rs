@@ -0,0 +1,37 @@
+#![cfg(feature = "disabled")]
+
+use reqwest_metrics::{test_util::install_debug_recorder, MetricsMiddleware};
+use reqwest_middleware::{reqwest, ClientBuilder};
+use tokio::test;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+#[test]
+async fn nothing_is_recorded() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddleware::builder()
+                .enable_active_requests()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let res = client.get(format!("{url}/hello")).send().await.unwrap();
+    assert_eq!(200, res.status().as_u16());
+
+    assert!(snapshotter.snapshot().into_vec().is_empty());
+}
@@ -0,0 +1,35 @@
+// Global defaults affect every `MetricsMiddleware::new` call, so they are tested in their own binary
+#![cfg(not(feature = "disabled"))]
+
+use reqwest_metrics::{BuilderError, MetricsMiddleware};
+
+#[test]
+fn set_global_defaults() {
+    let before = MetricsMiddleware::new();
+
+    let mut defaults = MetricsMiddleware::builder();
+    defaults
+        .http_request_method_label("method")
+        .request_duration_metric("requests.duration");
+    MetricsMiddleware::set_global_defaults(defaults).unwrap();
+
+    let metrics = MetricsMiddleware::new();
+    assert_eq!("method", metrics.http_request_method_label());
+    assert_eq!("requests.duration", metrics.request_duration_metric());
+    assert_eq!("http.request.method", before.http_request_method_label());
+    assert_eq!(
+        "http.request.method",
+        MetricsMiddleware::builder()
+            .build()
+            .http_request_method_label()
+    );
+
+    assert_eq!(
+        Err(BuilderError::GlobalDefaultsAlreadySet),
+        MetricsMiddleware::set_global_defaults(MetricsMiddleware::builder())
+    );
+    assert_eq!(
+        "method",
+        MetricsMiddleware::new().http_request_method_label()
+    );
+}
@@ -1,31 +1,48 @@
-use metrics_util::debugging::{DebuggingRecorder, Snapshotter};
-use reqwest_metrics::{MetricsMiddleware, MetricsMiddlewareBuilder};
-use reqwest_middleware::{reqwest, ClientBuilder};
+// Nothing is recorded with the `disabled` feature, see `disabled.rs`
+#![cfg(not(feature = "disabled"))]
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use http::{
+    header::{HeaderName, CONTENT_LENGTH},
+    Extensions, StatusCode,
+};
+use metrics::Unit;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use metrics_util::debugging::{DebugValue, Snapshotter};
+use reqwest_metrics::{
+    test_util::{self, install_debug_recorder},
+    BodySizeUnit, BuilderError, Clock, ConnectionDuration, ConnectionReused, DnsDuration,
+    DurationUnit, MetricsMiddleware, MetricsMiddlewareBuilder, MetricsSink, RecordingMode,
+    RequestAttempts, RequestClass, ResendCount, TlsVersion,
+};
+use reqwest_middleware::{reqwest, ClientBuilder, Middleware, Next};
 use tokio::test;
 use wiremock::{
     matchers::{method, path},
     Mock, MockServer, ResponseTemplate,
 };
 
-const SNAPSHOT_FILTERS: [(&'static str, &'static str); 4] = [
-    (
-        r"Histogram\(\s*[\s\S]*?\s*\)",
-        "Histogram([HISTOGRAM_VALUE])",
-    ),
-    (
-        r#"Label\(\s*"server.port"\s*,\s*[\s\S]*?\s*\)"#,
-        r#"Label("server.port", [PORT])"#,
-    ),
+/// The shared filters, plus the `port` label of the `custom_labels` test.
+const SNAPSHOT_FILTERS: [(&str, &str); 4] = [
+    test_util::SNAPSHOT_FILTERS[0],
+    test_util::SNAPSHOT_FILTERS[1],
+    test_util::SNAPSHOT_FILTERS[2],
     (
         r#"Label\(\s*"port"\s*,\s*[\s\S]*?\s*\)"#,
         r#"Label("port", [PORT])"#,
     ),
-    (r#"hash: \d*"#, "hash: [HASH]"),
 ];
 
 #[test]
 async fn basic() {
-    let snapshotter = install_debug_recorder();
+    let (snapshotter, _guard) = install_debug_recorder();
 
     let client = ClientBuilder::new(reqwest::Client::new())
         .with(MetricsMiddleware::new())
@@ -52,7 +69,7 @@ async fn basic() {
 
 #[test]
 async fn custom_labels() {
-    let snapshotter = install_debug_recorder();
+    let (snapshotter, _guard) = install_debug_recorder();
 
     let client = ClientBuilder::new(reqwest::Client::new())
         .with(
@@ -87,9 +104,3881 @@ async fn custom_labels() {
     });
 }
 
-fn install_debug_recorder() -> Snapshotter {
-    let recorder = DebuggingRecorder::new();
-    let snapshotter = recorder.snapshotter();
-    recorder.install().unwrap();
+#[test]
+async fn custom_counter_name() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .counter_name("requests.total")
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, unit, description, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "requests.total")
+        .expect("counter should be recorded under the custom name");
+    assert_eq!(&Some(Unit::Count), unit);
+    assert_eq!(
+        Some("Number of HTTP client requests."),
+        description.as_deref()
+    );
+    assert_eq!(&DebugValue::Counter(2), value);
+}
+
+#[test]
+async fn custom_descriptions() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .request_duration_description("Durée des requêtes HTTP.")
+                .response_body_size_description(String::from("Taille des réponses HTTP."))
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let description = |name| {
+        snapshot
+            .iter()
+            .find(|(key, ..)| key.key().name() == name)
+            .and_then(|(_, _, description, _)| description.as_deref())
+    };
+    assert_eq!(
+        Some("Durée des requêtes HTTP."),
+        description("http.client.request.duration")
+    );
+    assert_eq!(
+        Some("Taille des réponses HTTP."),
+        description("http.client.response.body.size")
+    );
+    assert_eq!(
+        Some("Size of HTTP client request bodies."),
+        description("http.client.request.body.size")
+    );
+}
+
+#[test]
+async fn duration_in_milliseconds() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .duration_unit(DurationUnit::Milliseconds)
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(50)))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, unit, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.duration")
+        .expect("duration histogram should be recorded");
+    assert_eq!(&Some(Unit::Milliseconds), unit);
+    let DebugValue::Histogram(values) = value else {
+        panic!("expected a histogram, got {value:?}");
+    };
+    assert!(values[0].into_inner() >= 50.0);
+}
+
+#[test]
+async fn body_size_in_kibibytes() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .body_size_unit(BodySizeUnit::Kibibytes)
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/upload"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0; 512]))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .post(format!("{url}/upload"))
+        .body(vec![0; 2048])
+        .send()
+        .await
+        .unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    for (name, size) in [
+        ("http.client.request.body.size", 2.0),
+        ("http.client.response.body.size", 0.5),
+    ] {
+        let (_, unit, _, value) = snapshot
+            .iter()
+            .find(|(key, ..)| key.key().name() == name)
+            .unwrap_or_else(|| panic!("{name} should be recorded"));
+        assert_eq!(&Some(Unit::Kibibytes), unit);
+        assert_eq!(&DebugValue::Histogram(vec![size.into()]), value);
+    }
+}
+
+#[test]
+async fn active_requests() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_active_requests()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (key, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.active_requests")
+        .expect("active requests gauge should be recorded");
+    let label_keys: Vec<_> = key.key().labels().map(|label| label.key()).collect();
+    assert_eq!(
+        vec!["http.request.method", "url.scheme", "server.address"],
+        label_keys
+    );
+    assert_eq!(&DebugValue::Gauge(0.0.into()), value);
+}
+
+#[test]
+async fn disabled_body_size_metrics() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .disable_request_body_size()
+                .disable_response_body_size()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let names: Vec<_> = snapshot
+        .iter()
+        .map(|(key, ..)| key.key().name().to_string())
+        .collect();
+    assert_eq!(
+        vec!["http.client.request.duration", "http.client.request.count"],
+        names
+    );
+}
+
+#[test]
+async fn status_class() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_status_class()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/missing")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("http.response.status_code".into(), "404".into())));
+    assert!(labels.contains(&("http.response.status_class".into(), "4xx".into())));
+    assert!(labels.iter().all(|(key, _)| key != "error.type"));
+}
+
+#[test]
+async fn treat_4xx_as_error() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .treat_4xx_as_error(true)
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/missing")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("error.type".into(), "404".into())));
+}
+
+#[test]
+async fn error_status_excludes() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .treat_4xx_as_error(true)
+                .error_status_excludes([StatusCode::NOT_FOUND])
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/gone"))
+        .respond_with(ResponseTemplate::new(410))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/missing")).send().await.unwrap();
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.iter().all(|(key, _)| key != "error.type"));
+
+    client.get(format!("{url}/gone")).send().await.unwrap();
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(|(key, ..)| key
+        .key()
+        .labels()
+        .any(|label| label.key() == "error.type" && label.value() == "410")));
+}
+
+#[test]
+async fn error_status_excludes_error_for_status() {
+    struct ErrorForStatusMiddleware;
+
+    #[async_trait::async_trait]
+    impl Middleware for ErrorForStatusMiddleware {
+        async fn handle(
+            &self,
+            req: reqwest::Request,
+            extensions: &mut Extensions,
+            next: Next<'_>,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            Ok(next.run(req, extensions).await?.error_for_status()?)
+        }
+    }
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .treat_4xx_as_error(true)
+                .error_status_excludes([StatusCode::NOT_FOUND])
+                .build(),
+        )
+        .with(ErrorForStatusMiddleware)
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/gone"))
+        .respond_with(ResponseTemplate::new(410))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .get(format!("{url}/missing"))
+        .send()
+        .await
+        .unwrap_err();
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("http.response.status_code".into(), "404".into())));
+    assert!(labels.iter().all(|(key, _)| key != "error.type"));
+
+    client.get(format!("{url}/gone")).send().await.unwrap_err();
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(|(key, ..)| {
+        let mut labels = key.key().labels();
+        labels.clone().any(|label| label.value() == "410")
+            && labels.any(|label| label.key() == "error.type")
+    }));
+}
+
+#[test]
+async fn network_protocol_labels_renamed_independently() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .network_protocol_name_label("protocol.name")
+                .network_protocol_version_label("protocol.version")
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("protocol.name".into(), "http".into())));
+    assert!(labels.contains(&("protocol.version".into(), "1.1".into())));
+    assert!(!labels
+        .iter()
+        .any(|(key, _)| key.starts_with("network.protocol")));
+}
+
+#[test]
+async fn url_template() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .url_template_fn(|url| {
+                    url.path()
+                        .starts_with("/users/")
+                        .then(|| "/users/{id}".to_string())
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/users/123"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/users/123")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("http.route".into(), "/users/{id}".into())));
+}
+
+#[test]
+async fn peer_service() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .peer_service("payments-api")
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("peer.service".into(), "payments-api".into())));
+}
+
+#[test]
+async fn constant_labels() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .constant_label("env", "prod")
+                .constant_label("region", "us-east-1")
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert_eq!(
+        vec![
+            ("env".to_string(), "prod".to_string()),
+            ("region".to_string(), "us-east-1".to_string())
+        ],
+        labels[..2]
+    );
+}
+
+#[test]
+async fn dynamic_labels() {
+    #[derive(Clone)]
+    struct Operation(&'static str);
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .dynamic_labels(|extensions| {
+                    extensions
+                        .get::<Operation>()
+                        .map(|operation| vec![("operation".to_string(), operation.0.to_string())])
+                        .unwrap_or_default()
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .get(format!("{url}/hello"))
+        .with_extension(Operation("getHello"))
+        .send()
+        .await
+        .unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("operation".into(), "getHello".into())));
+}
+
+#[test]
+async fn measure_response_body() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .measure_response_body()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("hello world"))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let res = client.get(format!("{url}/hello")).send().await.unwrap();
+    assert_eq!(format!("{url}/hello"), res.url().as_str());
+    assert_eq!(Some(11), res.content_length());
+    assert_eq!("hello world", res.text().await.unwrap());
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.response.body.size")
+        .expect("response body size should be recorded");
+    assert_eq!(&DebugValue::Histogram(vec![11.0.into()]), value);
+}
+
+#[test]
+async fn streaming_request_body_size() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .skip_unknown_request_body_size()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/upload"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .post(format!("{url}/upload"))
+        .header(CONTENT_LENGTH, 11)
+        .body(reqwest::Body::wrap("hello world".to_string()))
+        .send()
+        .await
+        .unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.body.size")
+        .expect("request body size should be recorded from the content-length header");
+    assert_eq!(&DebugValue::Histogram(vec![11.0.into()]), value);
+
+    client
+        .post(format!("{url}/upload"))
+        .body(reqwest::Body::wrap("hello world".to_string()))
+        .send()
+        .await
+        .unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.body.size")
+        .unwrap();
+    assert_eq!(&DebugValue::Histogram(vec![]), value);
+}
+
+#[test]
+async fn request_size_hint() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .request_size_hint(|req| {
+                    req.headers()
+                        .get("x-upload-size")?
+                        .to_str()
+                        .ok()?
+                        .parse()
+                        .ok()
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/upload"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .post(format!("{url}/upload"))
+        .header("x-upload-size", 11)
+        .body(reqwest::Body::wrap("hello world".to_string()))
+        .send()
+        .await
+        .unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.body.size")
+        .expect("request body size should be recorded from the hint");
+    assert_eq!(&DebugValue::Histogram(vec![11.0.into()]), value);
+
+    // Buffered bodies take precedence over the hint
+    client
+        .post(format!("{url}/upload"))
+        .header("x-upload-size", 11)
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.body.size")
+        .unwrap();
+    assert_eq!(&DebugValue::Histogram(vec![5.0.into()]), value);
+}
+
+#[test]
+async fn resend_count() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddleware::new())
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .get(format!("{url}/hello"))
+        .with_extension(ResendCount(2))
+        .send()
+        .await
+        .unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("http.request.resend_count".into(), "2".into())));
+}
+
+#[test]
+async fn cache_hit() {
+    #[derive(Clone)]
+    struct CacheHit(bool);
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .cache_status_extractor(|extensions| {
+                    extensions.get::<CacheHit>().map(|cache_hit| cache_hit.0)
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .get(format!("{url}/hello"))
+        .with_extension(CacheHit(true))
+        .send()
+        .await
+        .unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("cache.hit".into(), "true".into())));
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(
+        |(key, ..)| key.key().name() == "http.client.request.duration"
+            && key.key().labels().all(|label| label.key() != "cache.hit")
+    ));
+}
+
+#[test]
+async fn operation_name_label() {
+    #[derive(Clone)]
+    struct Operation(&'static str);
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .operation_name_extractor(|extensions| {
+                    extensions
+                        .get::<Operation>()
+                        .map(|operation| operation.0.to_string())
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/users/1"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .get(format!("{url}/users/1"))
+        .with_extension(Operation("getUser"))
+        .send()
+        .await
+        .unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("operation".into(), "getUser".into())));
+}
+
+#[test]
+async fn connection_duration() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_connection_duration()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .get(format!("{url}/hello"))
+        .with_extension(ConnectionDuration(Duration::from_millis(25)))
+        .send()
+        .await
+        .unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, unit, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.connection.duration")
+        .expect("connection duration should be recorded");
+    assert_eq!(&Some(Unit::Seconds), unit);
+    assert_eq!(&DebugValue::Histogram(vec![0.025.into()]), value);
+}
+
+#[test]
+async fn connection_reuse_label() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_connection_reuse_label()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    // The label is omitted when it is unknown
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.iter().all(|(key, _)| key != "connection.reused"));
+
+    client
+        .get(format!("{url}/hello"))
+        .with_extension(ConnectionReused(true))
+        .send()
+        .await
+        .unwrap();
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(
+        |(key, ..)| key.key().name() == "http.client.request.duration"
+            && key
+                .key()
+                .labels()
+                .any(|label| label.key() == "connection.reused" && label.value() == "true")
+    ));
+}
+
+#[test]
+async fn tls_version_label() {
+    let metrics = MetricsMiddlewareBuilder::new()
+        .enable_tls_version_label()
+        .build();
+    let client = reqwest::Client::new();
+    let req = client.get("https://example.com").build().unwrap();
+
+    // The label is omitted when the version is unknown
+    let res = Ok(reqwest::Response::from(http::Response::new("")));
+    let labels = metrics.compute_labels(&req, &res);
+    assert!(labels.iter().all(|(key, _)| key != "tls.protocol.version"));
+
+    // ie. inserted by a custom connector
+    let mut res = http::Response::new("");
+    res.extensions_mut().insert(TlsVersion("1.3"));
+    let res = Ok(reqwest::Response::from(res));
+    let labels = metrics.compute_labels(&req, &res);
+    assert!(labels.contains(&("tls.protocol.version".into(), "1.3".into())));
+}
+
+#[test]
+async fn dns_duration() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_dns_duration()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    // Nothing is recorded when the timing is unavailable
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot
+        .iter()
+        .all(|(key, ..)| key.key().name() != "http.client.dns.duration"));
+
+    client
+        .get(format!("{url}/hello"))
+        .with_extension(DnsDuration(Duration::from_millis(5)))
+        .send()
+        .await
+        .unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, unit, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.dns.duration")
+        .expect("dns duration should be recorded");
+    assert_eq!(&Some(Unit::Seconds), unit);
+    assert_eq!(&DebugValue::Histogram(vec![0.005.into()]), value);
+}
+
+#[test]
+async fn request_attempts() {
+    struct RetryMiddleware;
+
+    #[async_trait::async_trait]
+    impl Middleware for RetryMiddleware {
+        async fn handle(
+            &self,
+            req: reqwest::Request,
+            extensions: &mut Extensions,
+            next: Next<'_>,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            let retry = req.try_clone().unwrap();
+            next.clone().run(req, extensions).await?;
+            let res = next.run(retry, extensions).await;
+            extensions.insert(RequestAttempts {
+                count: 2,
+                durations: vec![Duration::from_millis(10), Duration::from_millis(20)],
+            });
+            res
+        }
+    }
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_request_attempts()
+                .build(),
+        )
+        .with(RetryMiddleware)
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, unit, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.attempts")
+        .expect("attempts should be recorded");
+    assert_eq!(&Some(Unit::Count), unit);
+    assert_eq!(&DebugValue::Histogram(vec![2.0.into()]), value);
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.attempt.duration")
+        .expect("attempt durations should be recorded");
+    assert_eq!(
+        &DebugValue::Histogram(vec![0.01.into(), 0.02.into()]),
+        value
+    );
+}
+
+#[cfg(feature = "tower")]
+#[test]
+async fn tower_layer() {
+    use std::convert::Infallible;
+
+    use reqwest_metrics::MetricsLayer;
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let service = ServiceBuilder::new()
+        .layer(MetricsLayer::new(
+            MetricsMiddlewareBuilder::new()
+                .http_request_method_label("method")
+                .build(),
+        ))
+        .service(service_fn(|_req: http::Request<String>| async {
+            Ok::<_, Infallible>(
+                http::Response::builder()
+                    .status(503)
+                    .body("unavailable".to_string())
+                    .unwrap(),
+            )
+        }));
+
+    let req = http::Request::post("http://example.com/hello")
+        .body("hello world".to_string())
+        .unwrap();
+    let res = service.oneshot(req).await.unwrap();
+    assert_eq!(503, res.status().as_u16());
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.body.size")
+        .unwrap();
+    assert_eq!(&DebugValue::Histogram(vec![11.0.into()]), value);
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert_eq!(
+        vec![
+            ("method".to_string(), "POST".to_string()),
+            ("url.scheme".to_string(), "http".to_string()),
+            ("network.protocol.name".to_string(), "http".to_string()),
+            ("server.address".to_string(), "example.com".to_string()),
+            ("server.port".to_string(), "80".to_string()),
+            ("network.protocol.version".to_string(), "1.1".to_string()),
+            ("http.response.status_code".to_string(), "503".to_string()),
+            ("error.type".to_string(), "503".to_string()),
+        ],
+        labels
+    );
+}
+
+#[test]
+async fn network_protocol_name_value() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .network_protocol_name_value("grpc")
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("network.protocol.name".into(), "grpc".into())));
+}
+
+#[cfg(feature = "tower")]
+#[test]
+async fn tower_websocket_protocol_name() {
+    use std::convert::Infallible;
+
+    use reqwest_metrics::MetricsLayer;
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let service = ServiceBuilder::new()
+        .layer(MetricsLayer::default())
+        .service(service_fn(|_req: http::Request<String>| async {
+            Ok::<_, Infallible>(
+                http::Response::builder()
+                    .status(101)
+                    .body(String::new())
+                    .unwrap(),
+            )
+        }));
+
+    let req = http::Request::get("wss://example.com/socket")
+        .body(String::new())
+        .unwrap();
+    service.oneshot(req).await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("network.protocol.name".into(), "websocket".into())));
+}
+
+#[cfg(feature = "tower")]
+#[test]
+async fn tower_record_duration_always() {
+    use std::convert::Infallible;
+
+    use reqwest_metrics::MetricsLayer;
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let service = ServiceBuilder::new()
+        .layer(MetricsLayer::new(
+            MetricsMiddlewareBuilder::new()
+                .sample_rate(0.0)
+                .record_duration_always(true)
+                .build(),
+        ))
+        .service(service_fn(|_req: http::Request<String>| async {
+            Ok::<_, Infallible>(http::Response::new(String::new()))
+        }));
+
+    let req = http::Request::get("http://example.com/hello")
+        .body(String::new())
+        .unwrap();
+    service.oneshot(req).await.unwrap();
+
+    let names: Vec<_> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, ..)| key.key().name().to_string())
+        .collect();
+    assert_eq!(vec!["http.client.request.duration".to_string()], names);
+}
+
+#[cfg(feature = "tower")]
+#[test]
+async fn tower_request_attempts() {
+    use std::convert::Infallible;
+
+    use reqwest_metrics::MetricsLayer;
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let service = ServiceBuilder::new()
+        .layer(MetricsLayer::new(
+            MetricsMiddlewareBuilder::new()
+                .enable_request_attempts()
+                .build(),
+        ))
+        .service(service_fn(|_req: http::Request<String>| async {
+            let mut res = http::Response::new(String::new());
+            res.extensions_mut().insert(RequestAttempts {
+                count: 2,
+                durations: vec![Duration::from_millis(10), Duration::from_millis(20)],
+            });
+            Ok::<_, Infallible>(res)
+        }));
+
+    let req = http::Request::get("http://example.com/hello")
+        .body(String::new())
+        .unwrap();
+    service.oneshot(req).await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.attempts")
+        .expect("attempts should be recorded");
+    assert_eq!(&DebugValue::Histogram(vec![2.0.into()]), value);
+}
+
+#[cfg(feature = "tower")]
+#[test]
+async fn tower_ignores_reqwest_only_options() {
+    use std::{
+        io,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use reqwest_metrics::MetricsLayer;
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let completed = Arc::new(AtomicBool::new(false));
+    let service = ServiceBuilder::new()
+        .layer(MetricsLayer::new(
+            MetricsMiddlewareBuilder::new()
+                .skip_if(|_| true)
+                .labels_from_request(|_| vec![("tenant".to_string(), "acme".to_string())])
+                .url_template_fn(|_| Some("/hello".to_string()))
+                .request_size_hint(|_| Some(42))
+                .enable_uri()
+                .error_type_fn(|_| Some("custom".into()))
+                .detailed_error_type(true)
+                .on_complete({
+                    let completed = Arc::clone(&completed);
+                    move |_, _, _| completed.store(true, Ordering::SeqCst)
+                })
+                .enable_timeout_counter()
+                .enable_redirect_count()
+                .build(),
+        ))
+        .service(service_fn(|_req: http::Request<String>| async {
+            Err::<http::Response<String>, _>(io::Error::new(io::ErrorKind::TimedOut, "timed out"))
+        }));
+
+    let req = http::Request::get("http://example.com/hello")
+        .body(String::new())
+        .unwrap();
+    service.oneshot(req).await.unwrap_err();
+
+    assert!(!completed.load(Ordering::SeqCst));
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.body.size")
+        .unwrap();
+    assert_eq!(&DebugValue::Histogram(vec![0.0.into()]), value);
+    assert!(!snapshot.iter().any(|(key, ..)| {
+        key.key().name() == "http.client.request.timeouts"
+            || key.key().name() == "http.client.redirect.count"
+    }));
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert_eq!(
+        vec![
+            ("http.request.method".to_string(), "GET".to_string()),
+            ("url.scheme".to_string(), "http".to_string()),
+            ("network.protocol.name".to_string(), "http".to_string()),
+            ("server.address".to_string(), "example.com".to_string()),
+            ("server.port".to_string(), "80".to_string()),
+            ("network.protocol.version".to_string(), "1.1".to_string()),
+            ("error.type".to_string(), "_OTHER".to_string()),
+        ],
+        labels
+    );
+}
+
+#[test]
+async fn custom_metric_names() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .request_duration_metric("internal.http.client.request.duration")
+                .request_body_size_metric("internal.http.client.request.body.size")
+                .response_body_size_metric("internal.http.client.response.body.size")
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let metrics: Vec<_> = snapshot
+        .iter()
+        .map(|(key, unit, ..)| (key.key().name().to_string(), *unit))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                "internal.http.client.request.duration".to_string(),
+                Some(Unit::Seconds)
+            ),
+            (
+                "internal.http.client.request.body.size".to_string(),
+                Some(Unit::Bytes)
+            ),
+            (
+                "internal.http.client.response.body.size".to_string(),
+                Some(Unit::Bytes)
+            ),
+            ("http.client.request.count".to_string(), Some(Unit::Count)),
+        ],
+        metrics
+    );
+}
+
+#[test]
+async fn gauge_summary_recording_mode() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .recording_mode(RecordingMode::GaugeSummary)
+                .disable_request_body_size()
+                .disable_response_body_size()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let gauge = |name: &str| {
+        let (_, _, _, value) = snapshot
+            .iter()
+            .find(|(key, ..)| key.key().name() == name)
+            .unwrap_or_else(|| panic!("{name} should be recorded"));
+        let DebugValue::Gauge(value) = value else {
+            panic!("expected a gauge, got {value:?}");
+        };
+        value.into_inner()
+    };
+    assert!(gauge("http.client.request.duration.min") <= gauge("http.client.request.duration.max"));
+    assert_eq!(2.0, gauge("http.client.request.duration.count"));
+    assert!(!snapshot
+        .iter()
+        .any(|(key, ..)| key.key().name() == "http.client.request.duration"));
+}
+
+#[test]
+async fn gauge_summary_shared_between_middlewares() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = || {
+        ClientBuilder::new(reqwest::Client::new())
+            .with(
+                MetricsMiddlewareBuilder::new()
+                    .recording_mode(RecordingMode::GaugeSummary)
+                    .build(),
+            )
+            .build()
+    };
+    let (first, second) = (client(), client());
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    first.get(format!("{url}/hello")).send().await.unwrap();
+    second.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.duration.count")
+        .expect("http.client.request.duration.count should be recorded");
+    assert_eq!(&DebugValue::Gauge(2.0.into()), value);
+}
+
+#[test]
+async fn gauge_summary_interval() {
+    struct ManualClock {
+        start: Instant,
+        elapsed_secs: Arc<AtomicU32>,
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            self.start + Duration::from_secs(self.elapsed_secs.load(Ordering::Relaxed).into())
+        }
+    }
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let elapsed_secs = Arc::new(AtomicU32::new(0));
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .recording_mode(RecordingMode::GaugeSummary)
+                .gauge_summary_interval(Duration::from_secs(10))
+                .with_clock(ManualClock {
+                    start: Instant::now(),
+                    elapsed_secs: Arc::clone(&elapsed_secs),
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let count = || {
+        let snapshot = snapshotter.snapshot().into_vec();
+        let (_, _, _, value) = snapshot
+            .into_iter()
+            .find(|(key, ..)| key.key().name() == "http.client.request.duration.count")
+            .expect("http.client.request.duration.count should be recorded");
+        value
+    };
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    assert_eq!(DebugValue::Gauge(2.0.into()), count());
+
+    elapsed_secs.store(10, Ordering::Relaxed);
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    assert_eq!(DebugValue::Gauge(1.0.into()), count());
+}
+
+#[test]
+async fn response_content_type() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_response_content_type()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json; charset=utf-8"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&(
+        "http.response.header.content-type".into(),
+        "application/json".into()
+    )));
+}
+
+#[test]
+async fn server_address_from_header() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .server_address_from_header(HeaderName::from_static("x-upstream-host"))
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .get(format!("{url}/hello"))
+        .header("x-upstream-host", "payments.internal")
+        .send()
+        .await
+        .unwrap();
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let server_addresses: Vec<_> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .filter(|(key, ..)| key.key().name() == "http.client.request.duration")
+        .filter_map(|(key, ..)| {
+            key.key()
+                .labels()
+                .find(|label| label.key() == "server.address")
+                .map(|label| label.value().to_string())
+        })
+        .collect();
+    assert_eq!(vec!["payments.internal", "127.0.0.1"], server_addresses);
+}
+
+#[test]
+async fn server_port_from_header() {
+    let metrics = MetricsMiddlewareBuilder::new()
+        .server_port_from_header(HeaderName::from_static("x-upstream-port"))
+        .build();
+    let client = reqwest::Client::new();
+    let res = Ok(reqwest::Response::from(http::Response::new("")));
+
+    for (upstream_port, port) in [
+        (Some("8443"), "8443"),
+        // Unparseable values fall back to the URL port
+        (Some("not-a-port"), "443"),
+        (Some("70000"), "443"),
+        (None, "443"),
+    ] {
+        let mut req = client.get("https://example.com/hello");
+        if let Some(upstream_port) = upstream_port {
+            req = req.header("x-upstream-port", upstream_port);
+        }
+        let labels = metrics.compute_labels(&req.build().unwrap(), &res);
+        assert!(
+            labels.contains(&("server.port".into(), port.into())),
+            "{upstream_port:?}: {labels:?}"
+        );
+    }
+}
+
+#[cfg(feature = "otel-trace-id")]
+#[test]
+async fn trace_id_label() {
+    use opentelemetry::trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+    };
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_trace_id_label()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let trace_id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+    let span_context = SpanContext::new(
+        trace_id,
+        SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    );
+    let context = opentelemetry::Context::new().with_remote_span_context(span_context);
+    {
+        let _context = context.attach();
+        client.get(format!("{url}/hello")).send().await.unwrap();
+    }
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("trace_id".into(), "4bf92f3577b34da6a3ce929d0e0e4736".into())));
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(
+        |(key, ..)| key.key().name() == "http.client.request.duration"
+            && key.key().labels().all(|label| label.key() != "trace_id")
+    ));
+}
+
+#[test]
+async fn skip_if() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .skip_if(|req| req.url().path() == "/healthz")
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/healthz")).send().await.unwrap();
+    assert!(snapshotter.snapshot().into_vec().is_empty());
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    assert!(!snapshotter.snapshot().into_vec().is_empty());
+}
+
+#[test]
+async fn cancelled_request() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddleware::new())
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let res = tokio::time::timeout(
+        Duration::from_millis(50),
+        client.get(format!("{url}/hello")).send(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("error.type".into(), "cancelled".into())));
+}
+
+#[test]
+async fn panicking_middleware() {
+    struct PanickingMiddleware;
+
+    #[async_trait::async_trait]
+    impl Middleware for PanickingMiddleware {
+        async fn handle(
+            &self,
+            _req: reqwest::Request,
+            _extensions: &mut Extensions,
+            _next: Next<'_>,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            panic!("middleware panicked");
+        }
+    }
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddleware::new())
+        .with(PanickingMiddleware)
+        .build();
+
+    let res = tokio::spawn(async move { client.get("http://localhost/hello").send().await }).await;
+    assert!(res.unwrap_err().is_panic());
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("error.type".into(), "panic".into())));
+}
+
+#[test]
+async fn reqwest_error_types() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(
+        reqwest::Client::builder()
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap(),
+    )
+    .with(MetricsMiddleware::new())
+    .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap_err();
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("error.type".into(), "timeout".into())));
+
+    // Nothing listens on port 1, so the connection is refused
+    client
+        .get("http://127.0.0.1:1/hello")
+        .send()
+        .await
+        .unwrap_err();
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(|(key, ..)| key
+        .key()
+        .labels()
+        .any(|label| label.key() == "error.type" && label.value() == "connect")));
+}
+
+#[test]
+async fn timeout_counter() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(
+        reqwest::Client::builder()
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap(),
+    )
+    .with(
+        MetricsMiddlewareBuilder::new()
+            .enable_timeout_counter()
+            .build(),
+    )
+    .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    client.get(format!("{url}/slow")).send().await.unwrap_err();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let timeouts: Vec<_> = snapshot
+        .iter()
+        .filter(|(key, ..)| key.key().name() == "http.client.request.timeouts")
+        .collect();
+    assert_eq!(1, timeouts.len());
+    let (key, _, _, value) = timeouts[0];
+    assert!(matches!(value, DebugValue::Counter(1)));
+    assert!(key
+        .key()
+        .labels()
+        .any(|label| label.key() == "http.request.method" && label.value() == "GET"));
+}
+
+#[test]
+async fn network_transport() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_network_transport()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("network.transport".into(), "tcp".into())));
+}
+
+#[test]
+async fn duration_buckets() {
+    let middleware = MetricsMiddlewareBuilder::new()
+        .duration_buckets(vec![0.5, 2.0])
+        .build();
+
+    let recorder = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(middleware.request_duration_metric().to_string()),
+            middleware.duration_buckets().unwrap(),
+        )
+        .unwrap()
+        .build_recorder();
+    let handle = recorder.handle();
+    let _guard = metrics::set_default_local_recorder(Box::leak(Box::new(recorder)));
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(middleware)
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let rendered = handle.render();
+    assert!(rendered.contains(r#"http_client_request_duration_bucket{"#));
+    assert!(rendered.contains(r#"le="2"} 1"#));
+}
+
+#[test]
+async fn body_size_buckets() {
+    let middleware = MetricsMiddlewareBuilder::new()
+        .request_body_size_buckets(vec![8.0, 1024.0])
+        .response_body_size_buckets(vec![16.0, 1024.0])
+        .try_build()
+        .unwrap();
+
+    let recorder = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(middleware.request_body_size_metric().to_string()),
+            middleware.request_body_size_buckets().unwrap(),
+        )
+        .unwrap()
+        .set_buckets_for_metric(
+            Matcher::Full(middleware.response_body_size_metric().to_string()),
+            middleware.response_body_size_buckets().unwrap(),
+        )
+        .unwrap()
+        .build_recorder();
+    let handle = recorder.handle();
+    let _guard = metrics::set_default_local_recorder(Box::leak(Box::new(recorder)));
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(middleware)
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("hello world"))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client
+        .post(format!("{url}/hello"))
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+
+    let rendered = handle.render();
+    assert!(rendered.contains(r#"http_client_request_body_size_bucket{"#));
+    assert!(rendered.contains(r#"le="8"} 1"#));
+    assert!(rendered.contains(r#"http_client_response_body_size_bucket{"#));
+    assert!(rendered.contains(r#"le="16"} 1"#));
+}
+
+#[test]
+async fn try_build_rejects_invalid_buckets() {
+    for buckets in [vec![], vec![2.0, 1.0], vec![f64::NAN]] {
+        let err = MetricsMiddlewareBuilder::new()
+            .response_body_size_buckets(buckets)
+            .try_build()
+            .unwrap_err();
+        assert_eq!(
+            BuilderError::InvalidBuckets {
+                metric: "http.client.response.body.size".to_string()
+            },
+            err
+        );
+    }
+}
+
+#[test]
+#[should_panic(expected = "the buckets of `http.client.request.duration` must be non-empty")]
+async fn build_rejects_nan_bucket() {
+    MetricsMiddlewareBuilder::new()
+        .duration_buckets(vec![f64::NAN])
+        .build();
+}
+
+#[test]
+async fn from_env() {
+    std::env::set_var("REQWEST_METRICS_METHOD_LABEL", "method");
+    std::env::set_var("REQWEST_METRICS_STATUS_LABEL", "status");
+    let middleware = MetricsMiddleware::from_env().unwrap();
+    assert_eq!("method", middleware.http_request_method_label());
+    assert_eq!("status", middleware.http_response_status_label());
+    assert_eq!("server.address", middleware.server_address_label());
+
+    std::env::set_var("REQWEST_METRICS_STATUS_LABEL", "");
+    let err = MetricsMiddleware::from_env().unwrap_err();
+    assert_eq!(
+        BuilderError::InvalidEnvVar {
+            name: "REQWEST_METRICS_STATUS_LABEL"
+        },
+        err
+    );
+
+    std::env::set_var("REQWEST_METRICS_STATUS_LABEL", "method");
+    let err = MetricsMiddleware::from_env().unwrap_err();
+    assert!(matches!(err, BuilderError::DuplicateLabelName { .. }));
+
+    std::env::remove_var("REQWEST_METRICS_METHOD_LABEL");
+    std::env::remove_var("REQWEST_METRICS_STATUS_LABEL");
+}
+
+#[test]
+async fn label_name_getters() {
+    let middleware = MetricsMiddlewareBuilder::new()
+        .http_request_method_label("method")
+        .server_address_label("host")
+        .build();
+
+    assert_eq!("method", middleware.http_request_method_label());
+    assert_eq!("host", middleware.server_address_label());
+    assert_eq!("url.scheme", middleware.url_scheme_label());
+}
+
+#[test]
+async fn record_request_size_eagerly() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .record_request_size_eagerly()
+                .build(),
+        )
+        .build();
+
+    // Nothing listens on port 1, so the request fails without a response
+    client
+        .post("http://127.0.0.1:1/hello")
+        .body("hello")
+        .send()
+        .await
+        .unwrap_err();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let mut body_sizes = snapshot
+        .iter()
+        .filter(|(key, ..)| key.key().name() == "http.client.request.body.size");
+    let (key, _, _, value) = body_sizes
+        .next()
+        .expect("request body size should be recorded");
+    assert!(body_sizes.next().is_none());
+    let labels: Vec<_> = key.key().labels().map(|label| label.key()).collect();
+    assert_eq!(
+        vec!["http.request.method", "url.scheme", "server.address"],
+        labels
+    );
+    let DebugValue::Histogram(values) = value else {
+        panic!("expected a histogram, got {value:?}");
+    };
+    assert_eq!(
+        vec![5.0],
+        values.iter().map(|v| v.into_inner()).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+async fn omit_default_port() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .omit_default_port(true)
+                .build(),
+        )
+        .build();
+
+    // The labels are recorded whether or not anything is listening on port 80
+    let _ = client.get("http://127.0.0.1/hello").send().await;
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.iter().all(|(key, _)| key != "server.port"));
+
+    let _ = client.get("http://127.0.0.1:1/hello").send().await;
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(|(key, ..)| key
+        .key()
+        .labels()
+        .any(|label| label.key() == "server.port" && label.value() == "1")));
+}
+
+#[test]
+async fn instrumented_client() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = reqwest_metrics::instrumented_client(reqwest::Client::new());
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("http.response.status_code".into(), "200".into())));
+}
+
+#[test]
+async fn redirect_count() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_redirect_count()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/old"))
+        .respond_with(ResponseTemplate::new(301).insert_header("location", "/hello"))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    client.get(format!("{url}/old")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let redirects: Vec<_> = snapshot
+        .iter()
+        .filter(|(key, ..)| key.key().name() == "http.client.redirect.count")
+        .map(|(.., value)| value)
+        .collect();
+    assert!(matches!(redirects[..], [DebugValue::Counter(1)]));
+}
+
+#[test]
+async fn custom_clock() {
+    /// A clock that advances by 250ms every time it is read.
+    struct SteppingClock {
+        start: Instant,
+        reads: AtomicU32,
+    }
+
+    impl Clock for SteppingClock {
+        fn now(&self) -> Instant {
+            let reads = self.reads.fetch_add(1, Ordering::Relaxed);
+            self.start + Duration::from_millis(250) * reads
+        }
+    }
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .with_clock(SteppingClock {
+                    start: Instant::now(),
+                    reads: AtomicU32::new(0),
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (.., value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.duration")
+        .expect("duration histogram should be recorded");
+    let DebugValue::Histogram(values) = value else {
+        panic!("expected a histogram, got {value:?}");
+    };
+    assert_eq!(0.25, values[0].into_inner());
+}
+
+#[test]
+async fn total_duration_counter() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let metrics = MetricsMiddlewareBuilder::new()
+        .enable_total_duration_counter()
+        .build();
+    let client = reqwest::Client::new();
+    let req = client.get(format!("{url}/hello")).build().unwrap();
+    for duration in [Duration::from_millis(30), Duration::from_millis(20)] {
+        let res = client
+            .execute(req.try_clone().unwrap())
+            .await
+            .map_err(Into::into);
+        metrics.record(&req, &res, duration);
+    }
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, unit, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.duration.total")
+        .expect("total duration should be recorded");
+    assert_eq!(&Some(Unit::Microseconds), unit);
+    assert_eq!(&DebugValue::Counter(50_000), value);
+}
+
+#[test]
+async fn slow_request_threshold() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let metrics = MetricsMiddlewareBuilder::new()
+        .slow_request_threshold(Duration::from_millis(100))
+        .build();
+    let client = reqwest::Client::new();
+    let req = client.get(format!("{url}/hello")).build().unwrap();
+    for duration in [50, 150, 200].map(Duration::from_millis) {
+        let res = client
+            .execute(req.try_clone().unwrap())
+            .await
+            .map_err(Into::into);
+        metrics.record(&req, &res, duration);
+    }
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (key, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.slow.count")
+        .expect("slow requests should be counted");
+    assert_eq!(&DebugValue::Counter(2), value);
+    let labels: Vec<_> = key.key().labels().map(|label| label.key()).collect();
+    assert_eq!(vec!["http.request.method", "server.address"], labels);
+}
+
+#[test]
+async fn collapse_unknown_methods() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("PURGE"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+    let purge = reqwest::Method::from_bytes(b"PURGE").unwrap();
+
+    for (collapse, expected) in [(true, "_OTHER"), (false, "PURGE")] {
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(
+                MetricsMiddlewareBuilder::new()
+                    .collapse_unknown_methods(collapse)
+                    .build(),
+            )
+            .build();
+
+        client
+            .request(purge.clone(), format!("{url}/hello"))
+            .send()
+            .await
+            .unwrap();
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        assert!(snapshot.iter().any(|(key, ..)| key
+            .key()
+            .labels()
+            .any(|label| label.key() == "http.request.method" && label.value() == expected)));
+    }
+}
+
+#[test]
+async fn middleware_error_type() {
+    struct RejectingMiddleware;
+
+    #[async_trait::async_trait]
+    impl Middleware for RejectingMiddleware {
+        async fn handle(
+            &self,
+            _req: reqwest::Request,
+            _extensions: &mut Extensions,
+            _next: Next<'_>,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            Err(reqwest_middleware::Error::middleware(
+                std::io::Error::other("rejected by policy"),
+            ))
+        }
+    }
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddleware::new())
+        .with(RejectingMiddleware)
+        .build();
+    client.get("http://example.com").send().await.unwrap_err();
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("error.type".into(), "middleware".into())));
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .detailed_error_type(true)
+                .build(),
+        )
+        .with(RejectingMiddleware)
+        .build();
+    client.get("http://example.com").send().await.unwrap_err();
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(|(key, ..)| key
+        .key()
+        .labels()
+        .any(|label| label.key() == "error.type" && label.value() == "rejected by policy")));
+}
+
+#[test]
+async fn short_circuiting_middleware() {
+    struct RejectingMiddleware;
+
+    #[async_trait::async_trait]
+    impl Middleware for RejectingMiddleware {
+        async fn handle(
+            &self,
+            _req: reqwest::Request,
+            _extensions: &mut Extensions,
+            _next: Next<'_>,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            Err(reqwest_middleware::Error::middleware(
+                std::io::Error::other("rate limited"),
+            ))
+        }
+    }
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    // Rejected before reaching the metrics middleware
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(RejectingMiddleware)
+        .with(MetricsMiddleware::new())
+        .build();
+    client.get("http://example.com").send().await.unwrap_err();
+    assert!(snapshotter.snapshot().into_vec().is_empty());
+
+    // Rejected after the metrics middleware
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddleware::new())
+        .with(RejectingMiddleware)
+        .build();
+    client.get("http://example.com").send().await.unwrap_err();
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("error.type".into(), "middleware".into())));
+    assert!(labels.contains(&("http.request.method".into(), "GET".into())));
+    assert!(labels.contains(&("server.address".into(), "example.com".into())));
+    assert!(labels
+        .iter()
+        .all(|(key, _)| key != "http.response.status_code"));
+}
+
+#[test]
+async fn status_from_error() {
+    struct ErrorForStatusMiddleware;
+
+    #[async_trait::async_trait]
+    impl Middleware for ErrorForStatusMiddleware {
+        async fn handle(
+            &self,
+            req: reqwest::Request,
+            extensions: &mut Extensions,
+            next: Next<'_>,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            Ok(next.run(req, extensions).await?.error_for_status()?)
+        }
+    }
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddleware::new())
+        .with(ErrorForStatusMiddleware)
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap_err();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("http.response.status_code".into(), "503".into())));
+}
+
+#[test]
+async fn labels_from_request() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .labels_from_request(|req| {
+                    vec![
+                        (
+                            "server.address".to_string(),
+                            "rewritten.internal".to_string(),
+                        ),
+                        ("path".to_string(), req.url().path().to_string()),
+                    ]
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("server.address".into(), "rewritten.internal".into())));
+    assert!(labels.contains(&("path".into(), "/hello".into())));
+    assert_eq!(
+        1,
+        labels
+            .iter()
+            .filter(|(key, _)| key == "server.address")
+            .count()
+    );
+}
+
+#[test]
+async fn shared_middleware() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let middleware: &'static MetricsMiddleware = Box::leak(Box::new(MetricsMiddleware::new()));
+    let clients = [
+        ClientBuilder::new(reqwest::Client::new())
+            .with(middleware)
+            .build(),
+        ClientBuilder::new(reqwest::Client::new())
+            .with(middleware)
+            .build(),
+    ];
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    for client in clients {
+        client.get(format!("{url}/hello")).send().await.unwrap();
+    }
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (.., value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.count")
+        .expect("request count should be recorded");
+    assert!(matches!(value, DebugValue::Counter(2)));
+}
+
+#[test]
+async fn url_full() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddlewareBuilder::new().enable_uri().build())
+        .build();
+    client
+        .get(format!("{url}/hello?token=secret"))
+        .send()
+        .await
+        .unwrap();
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("url.full".into(), format!("{url}/hello"))));
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_uri()
+                .url_redactor(|url| url.path().to_string())
+                .build(),
+        )
+        .build();
+    client
+        .get(format!("{url}/hello?token=secret"))
+        .send()
+        .await
+        .unwrap();
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(|(key, ..)| key
+        .key()
+        .labels()
+        .any(|label| label.key() == "url.full" && label.value() == "/hello")));
+}
+
+#[test]
+async fn short_label_aliases() {
+    let metrics = MetricsMiddlewareBuilder::new()
+        .method_label("method")
+        .status_label("status")
+        .host_label("host")
+        .build();
+    assert_eq!(metrics.http_request_method_label(), "method");
+    assert_eq!(metrics.http_response_status_label(), "status");
+    assert_eq!(metrics.server_address_label(), "host");
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+async fn tracing_events() {
+    use std::{
+        fmt::{Debug, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{field::Field, Event, Level, Subscriber};
+    use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+    /// Collects the fields of every event as a string.
+    #[derive(Clone, Default)]
+    struct Events(Arc<Mutex<Vec<String>>>);
+
+    impl<S: Subscriber> Layer<S> for Events {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut fields = String::new();
+            event.record(&mut |field: &Field, value: &dyn Debug| {
+                write!(fields, "{}={:?};", field.name(), value).unwrap();
+            });
+            self.0.lock().unwrap().push(fields);
+        }
+    }
+
+    let (_snapshotter, _guard) = install_debug_recorder();
+    let events = Events::default();
+    let _subscriber = tracing_subscriber::registry()
+        .with(events.clone())
+        .set_default();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .emit_tracing_events(Level::INFO)
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let events = events.0.lock().unwrap();
+    let event = events
+        .iter()
+        .find(|event| event.contains("HTTP client request completed"))
+        .expect("an event should be emitted for the request");
+    assert!(event.contains("http.request.method=GET"));
+    assert!(event.contains("http.response.status_code=200"));
+    assert!(event.contains("duration="));
+}
+
+#[test]
+async fn try_build_rejects_duplicate_label_names() {
+    let err = MetricsMiddlewareBuilder::new()
+        .http_request_method_label("name")
+        .server_address_label("name")
+        .try_build()
+        .unwrap_err();
+    assert_eq!(
+        BuilderError::DuplicateLabelName {
+            name: "name".to_string(),
+            labels: ("http.request.method", "server.address"),
+        },
+        err
+    );
+    assert_eq!(
+        "the `http.request.method` and `server.address` labels are both named `name`",
+        err.to_string()
+    );
+
+    assert!(MetricsMiddlewareBuilder::new()
+        .http_request_method_label("method")
+        .server_address_label("host")
+        .try_build()
+        .is_ok());
+}
+
+#[test]
+async fn sample_rate() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddlewareBuilder::new().sample_rate(0.0).build())
+        .build();
+    for _ in 0..10 {
+        client.get(format!("{url}/hello")).send().await.unwrap();
+    }
+    assert!(snapshotter.snapshot().into_vec().is_empty());
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddlewareBuilder::new().sample_rate(1.0).build())
+        .build();
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.count")
+        .unwrap();
+    assert!(matches!(value, DebugValue::Counter(1)));
+}
+
+#[test]
+async fn record_manually() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("hello world"))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let metrics = MetricsMiddleware::new();
+    let client = reqwest::Client::new();
+    let req = client.get(format!("{url}/hello")).build().unwrap();
+    let res = client
+        .execute(req.try_clone().unwrap())
+        .await
+        .map_err(Into::into);
+    metrics.record(&req, &res, Duration::from_millis(30));
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (key, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.duration")
+        .unwrap();
+    assert_eq!(&DebugValue::Histogram(vec![0.03.into()]), value);
+    assert!(key
+        .key()
+        .labels()
+        .any(|label| label.key() == "http.response.status_code" && label.value() == "200"));
+
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.response.body.size")
+        .unwrap();
+    assert_eq!(&DebugValue::Histogram(vec![11.0.into()]), value);
+}
+
+#[test]
+async fn compute_labels() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let metrics = MetricsMiddleware::new();
+    let client = reqwest::Client::new();
+    let req = client.get(format!("{url}/hello")).build().unwrap();
+    let res = client
+        .execute(req.try_clone().unwrap())
+        .await
+        .map_err(Into::into);
+
+    let labels: Vec<_> = metrics
+        .compute_labels(&req, &res)
+        .into_iter()
+        .map(|(key, value)| (key, value.into_owned()))
+        .collect();
+    assert!(labels.contains(&("http.response.status_code".into(), "404".into())));
+
+    metrics.record(&req, &res, Duration::from_millis(30));
+    assert_eq!(
+        labels,
+        metric_labels(&snapshotter, "http.client.request.duration")
+    );
+}
+
+#[test]
+async fn server_address_ip_literals() {
+    let metrics = MetricsMiddleware::new();
+    let client = reqwest::Client::new();
+
+    for (url, address) in [
+        ("http://[::1]:8080/hello", "::1"),
+        ("http://127.0.0.1/hello", "127.0.0.1"),
+        ("http://example.com/hello", "example.com"),
+    ] {
+        let req = client.get(url).build().unwrap();
+        let res = Ok(reqwest::Response::from(http::Response::new("")));
+        let labels = metrics.compute_labels(&req, &res);
+        assert!(
+            labels.contains(&("server.address".into(), address.into())),
+            "{url}: {labels:?}"
+        );
+    }
+}
+
+#[test]
+async fn max_address_cardinality() {
+    let metrics = MetricsMiddlewareBuilder::new()
+        .max_address_cardinality(2)
+        .build();
+    let client = reqwest::Client::new();
+
+    let address = |metrics: &MetricsMiddleware, url: &str| {
+        let req = client.get(url).build().unwrap();
+        let res = Ok(reqwest::Response::from(http::Response::new("")));
+        metrics
+            .compute_labels(&req, &res)
+            .into_iter()
+            .find(|(key, _)| key == "server.address")
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    };
+    let record = |metrics: &MetricsMiddleware, url: &str| {
+        let req = client.get(url).build().unwrap();
+        let res = Ok(reqwest::Response::from(http::Response::new("")));
+        metrics.record(&req, &res, Duration::from_millis(10));
+    };
+
+    // Computing labels does not use up the addresses
+    assert_eq!("c.example.com", address(&metrics, "http://c.example.com"));
+    assert_eq!("d.example.com", address(&metrics, "http://d.example.com"));
+
+    record(&metrics, "http://a.example.com");
+    record(&metrics, "http://b.example.com");
+    assert_eq!("a.example.com", address(&metrics, "http://a.example.com"));
+    assert_eq!("b.example.com", address(&metrics, "http://b.example.com"));
+    assert_eq!("_OTHER", address(&metrics, "http://c.example.com"));
+    // Addresses seen before the limit was reached are still recorded, also by clones
+    assert_eq!(
+        "a.example.com",
+        address(&metrics.clone(), "http://a.example.com")
+    );
+}
+
+#[test]
+async fn client_name() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    for name in ["payments", "search"] {
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(
+                MetricsMiddlewareBuilder::new()
+                    .client_name(name)
+                    .enable_active_requests()
+                    .enable_status_counter()
+                    .slow_request_threshold(Duration::ZERO)
+                    .enable_last_duration_gauge()
+                    .build(),
+            )
+            .build();
+        client.get(format!("{url}/hello")).send().await.unwrap();
+    }
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    for metric in [
+        "http.client.request.duration",
+        "http.client.active_requests",
+        "http.client.response.count",
+        "http.client.request.slow.count",
+        "http.client.request.last_duration",
+    ] {
+        for name in ["payments", "search"] {
+            assert!(
+                snapshot.iter().any(|(key, ..)| key.key().name() == metric
+                    && key
+                        .key()
+                        .labels()
+                        .any(|label| label.key() == "http.client.name" && label.value() == name)),
+                "{metric} is not recorded for {name}"
+            );
+        }
+    }
+    assert!(snapshot.iter().all(|(key, ..)| key
+        .key()
+        .labels()
+        .any(|label| label.key() == "http.client.name")));
+}
+
+#[test]
+async fn started_counter() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_started_counter()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (key, unit, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.started")
+        .expect("started counter should be recorded");
+    assert_eq!(&Some(Unit::Count), unit);
+    assert_eq!(&DebugValue::Counter(1), value);
+    let labels: Vec<_> = key.key().labels().map(|label| label.key()).collect();
+    assert_eq!(
+        vec!["http.request.method", "url.scheme", "server.address"],
+        labels
+    );
+}
+
+#[test]
+async fn stable_label_order() {
+    let metrics = MetricsMiddlewareBuilder::new()
+        .omit_default_port(true)
+        .build();
+    let client = reqwest::Client::new();
+
+    let label_keys = |url: &str| {
+        let req = client.get(url).build().unwrap();
+        let res = Ok(reqwest::Response::from(http::Response::new("")));
+        metrics
+            .compute_labels(&req, &res)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>()
+    };
+
+    let with_port = label_keys("http://example.com:8080");
+    let without_port = label_keys("http://example.com");
+    assert!(with_port.contains(&"server.port".to_string()));
+    assert_eq!(
+        with_port
+            .into_iter()
+            .filter(|key| key != "server.port")
+            .collect::<Vec<_>>(),
+        without_port
+    );
+
+    // Custom labels come last, after the labels read from the extensions
+    let metrics = MetricsMiddlewareBuilder::new()
+        .constant_label("env", "test")
+        .labels_from_request(|_| vec![("tenant".to_string(), "acme".to_string())])
+        .dynamic_labels(|_| vec![("shard".to_string(), "1".to_string())])
+        .enable_connection_reuse_label()
+        .enable_tls_version_label()
+        .build();
+    let req = client.get("https://example.com").build().unwrap();
+    let mut res = http::Response::new("");
+    res.extensions_mut().insert(ConnectionReused(true));
+    res.extensions_mut().insert(TlsVersion("1.3"));
+    let res = Ok(reqwest::Response::from(res));
+    let keys: Vec<_> = metrics
+        .compute_labels(&req, &res)
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(
+        vec![
+            "env",
+            "http.request.method",
+            "url.scheme",
+            "network.protocol.name",
+            "server.address",
+            "server.port",
+            "network.protocol.version",
+            "http.response.status_code",
+            "connection.reused",
+            "tls.protocol.version",
+            "tenant",
+            "shard",
+        ],
+        keys
+    );
+}
+
+#[test]
+async fn user_agent_label() {
+    let client = reqwest::Client::new();
+    let res = Ok(reqwest::Response::from(http::Response::new("")));
+
+    let metrics = MetricsMiddlewareBuilder::new()
+        .enable_user_agent_label()
+        .build();
+
+    // The label is omitted when the header is absent
+    let req = client.get("https://example.com").build().unwrap();
+    let labels = metrics.compute_labels(&req, &res);
+    assert!(labels.iter().all(|(key, _)| key != "user_agent.original"));
+
+    let req = client
+        .get("https://example.com")
+        .header("user-agent", "my-app/1.2.3")
+        .build()
+        .unwrap();
+    let labels = metrics.compute_labels(&req, &res);
+    assert!(labels.contains(&("user_agent.original".into(), "my-app/1.2.3".into())));
+
+    let metrics = MetricsMiddlewareBuilder::new()
+        .enable_user_agent_label()
+        .user_agent_map(|user_agent| match user_agent.split_once('/') {
+            Some((product, _)) => product.to_string().into(),
+            None => user_agent.into(),
+        })
+        .build();
+    let labels = metrics.compute_labels(&req, &res);
+    assert!(labels.contains(&("user_agent.original".into(), "my-app".into())));
+}
+
+#[test]
+async fn header_count() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).insert_header("x-response", "1"))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_header_count()
+                .enable_response_header_count()
+                .build(),
+        )
+        .build();
+    client
+        .get(format!("{url}/hello"))
+        .header("x-first", "1")
+        .header("x-second", "2")
+        .send()
+        .await
+        .unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.request.header.count")
+        .unwrap();
+    assert_eq!(&DebugValue::Histogram(vec![2.0.into()]), value);
+
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.response.header.count")
+        .unwrap();
+    let DebugValue::Histogram(values) = value else {
+        panic!("expected a histogram");
+    };
+    assert_eq!(1, values.len());
+    assert!(values[0].into_inner() >= 1.0);
+}
+
+#[test]
+async fn strict_otel_labels() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .client_name("payments")
+                .constant_label("env", "test")
+                .url_template_fn(|_| Some("/hello".to_string()))
+                .enable_active_requests()
+                .strict_otel_labels()
+                .build(),
+        )
+        .build();
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let labels = |name| {
+        let (key, ..) = snapshot
+            .iter()
+            .find(|(key, ..)| key.key().name() == name)
+            .unwrap();
+        key.key()
+            .labels()
+            .map(|label| label.key().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    for name in [
+        "http.client.request.duration",
+        "http.client.request.body.size",
+        "http.client.response.body.size",
+    ] {
+        let labels = labels(name);
+        assert!(labels.contains(&"http.response.status_code".to_string()));
+        assert!(labels.contains(&"env".to_string()));
+        assert!(!labels.contains(&"http.client.name".to_string()));
+        assert!(!labels.contains(&"http.route".to_string()));
+    }
+    assert!(!labels("http.client.active_requests").contains(&"http.client.name".to_string()));
+    assert!(labels("http.client.request.count").contains(&"http.client.name".to_string()));
+    assert!(labels("http.client.request.count").contains(&"http.route".to_string()));
+}
+
+#[test]
+async fn time_to_first_byte() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let metrics = MetricsMiddlewareBuilder::new()
+        .enable_time_to_first_byte()
+        .build();
+    let req = reqwest::Client::new()
+        .get("https://example.com")
+        .build()
+        .unwrap();
+
+    let res = Ok(reqwest::Response::from(http::Response::new("")));
+    metrics.record(&req, &res, Duration::from_millis(250));
+
+    // Failed requests never received a first byte
+    let res = Err(reqwest_middleware::Error::middleware(
+        std::io::Error::other("rejected by policy"),
+    ));
+    metrics.record(&req, &res, Duration::from_millis(500));
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let values: Vec<_> = snapshot
+        .iter()
+        .filter(|(key, ..)| key.key().name() == "http.client.response.time_to_first_byte")
+        .map(|(_, _, _, value)| value)
+        .collect();
+    assert_eq!(vec![&DebugValue::Histogram(vec![0.25.into()])], values);
+}
+
+#[test]
+async fn time_to_first_byte_before_response_body() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("hello world"))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_time_to_first_byte()
+                .measure_response_body()
+                .client_name("payments")
+                .strict_otel_labels()
+                .build(),
+        )
+        .build();
+    let res = client.get(format!("{url}/hello")).send().await.unwrap();
+
+    // Recorded before the measured response body is read, with the labels of the duration
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(!snapshot.iter().any(|(key, _, _, value)| key.key().name()
+        == "http.client.response.body.size"
+        && *value != DebugValue::Histogram(Vec::new())));
+    let (key, ..) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.response.time_to_first_byte")
+        .expect("time to first byte should be recorded");
+    assert!(!key
+        .key()
+        .labels()
+        .any(|label| label.key() == "http.client.name"));
+
+    assert_eq!("hello world", res.text().await.unwrap());
+}
+
+#[test]
+async fn request_class_label() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddleware::new())
+        .build();
+    client
+        .get(format!("{url}/hello"))
+        .with_extension(RequestClass("batch"))
+        .send()
+        .await
+        .unwrap();
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| {
+            key.key().name() == "http.client.request.count"
+                && key
+                    .key()
+                    .labels()
+                    .any(|label| label.key() == "request.class" && label.value() == "batch")
+        })
+        .unwrap();
+    assert_eq!(&DebugValue::Counter(1), value);
+
+    // The label is omitted when absent
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| {
+            key.key().name() == "http.client.request.count"
+                && key
+                    .key()
+                    .labels()
+                    .all(|label| label.key() != "request.class")
+        })
+        .unwrap();
+    assert_eq!(&DebugValue::Counter(1), value);
+}
+
+#[test]
+async fn treat_upgrades_specially() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let req = reqwest::Client::new()
+        .get("http://example.com/socket")
+        .build()
+        .unwrap();
+    let res = || {
+        let res = http::Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header("upgrade", "websocket")
+            .body("")
+            .unwrap();
+        Ok(reqwest::Response::from(res))
+    };
+
+    // 101 responses are recorded with their status code, and are not errors
+    let metrics = MetricsMiddleware::new();
+    let labels = metrics.compute_labels(&req, &res());
+    assert!(labels.contains(&("http.response.status_code".into(), "101".into())));
+    assert!(labels.contains(&("network.protocol.name".into(), "http".into())));
+    assert!(labels.iter().all(|(key, _)| key != "error.type"));
+
+    let metrics = MetricsMiddlewareBuilder::new()
+        .treat_upgrades_specially()
+        .build();
+    let labels = metrics.compute_labels(&req, &res());
+    assert!(labels.contains(&("network.protocol.name".into(), "websocket".into())));
+
+    metrics.record(&req, &res(), Duration::from_millis(10));
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot
+        .iter()
+        .any(|(key, ..)| key.key().name() == "http.client.upgrade.duration"));
+    assert!(snapshot
+        .iter()
+        .all(|(key, ..)| key.key().name() != "http.client.request.duration"));
+}
+
+#[test]
+async fn record_duration_always() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Hello"))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .sample_rate(0.0)
+                .record_duration_always(true)
+                .build(),
+        )
+        .build();
+    for _ in 0..3 {
+        client.get(format!("{url}/hello")).send().await.unwrap();
+    }
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let names: Vec<_> = snapshot.iter().map(|(key, ..)| key.key().name()).collect();
+    assert_eq!(vec!["http.client.request.duration"], names);
+    let (key, _, _, DebugValue::Histogram(values)) = &snapshot[0] else {
+        panic!("expected a histogram");
+    };
+    assert_eq!(3, values.len());
+    assert!(key
+        .key()
+        .labels()
+        .any(|label| label.key() == "http.response.status_code" && label.value() == "200"));
+}
+
+#[test]
+async fn server_address_lowercase() {
+    let client = reqwest::Client::new();
+    let res = Ok(reqwest::Response::from(http::Response::new("")));
+    let server_address = |metrics: &MetricsMiddleware, req: &reqwest::Request| {
+        metrics
+            .compute_labels(req, &res)
+            .into_iter()
+            .find(|(key, _)| key == "server.address")
+            .map(|(_, value)| value.into_owned())
+    };
+
+    let metrics = MetricsMiddlewareBuilder::new()
+        .server_address_from_header(HeaderName::from_static("x-upstream"))
+        .build();
+    for (upstream, address) in [
+        ("API.Example.com", "api.example.com"),
+        ("api.example.com", "api.example.com"),
+        ("2001:DB8::1", "2001:DB8::1"),
+    ] {
+        let req = client
+            .get("http://localhost/hello")
+            .header("x-upstream", upstream)
+            .build()
+            .unwrap();
+        assert_eq!(Some(address.to_string()), server_address(&metrics, &req));
+    }
+}
+
+#[cfg(feature = "tower")]
+#[test]
+async fn tower_server_address_lowercase() {
+    use std::convert::Infallible;
+
+    use reqwest_metrics::MetricsLayer;
+    use tower::{service_fn, Service, ServiceBuilder, ServiceExt};
+
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mut service = ServiceBuilder::new()
+        .layer(MetricsLayer::default())
+        .service(service_fn(|_req: http::Request<String>| async {
+            Ok::<_, Infallible>(http::Response::new(String::new()))
+        }));
+
+    // Unlike `reqwest::Url`, `http::Uri` keeps the case of the host
+    for uri in [
+        "http://Example.COM/hello",
+        "http://example.com/hello",
+        "http://[2001:DB8::1]/hello",
+    ] {
+        let req = http::Request::get(uri).body(String::new()).unwrap();
+        service.ready().await.unwrap().call(req).await.unwrap();
+    }
+
+    let mut server_addresses: Vec<_> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .filter(|(key, ..)| key.key().name() == "http.client.request.count")
+        .map(|(key, _, _, value)| {
+            let server_address = key
+                .key()
+                .labels()
+                .find(|label| label.key() == "server.address")
+                .map(|label| label.value().to_string())
+                .unwrap();
+            (server_address, value)
+        })
+        .collect();
+    server_addresses.sort_by(|(a, _), (b, _)| a.cmp(b));
+    assert_eq!(
+        vec![
+            ("2001:DB8::1".to_string(), DebugValue::Counter(1)),
+            ("example.com".to_string(), DebugValue::Counter(2)),
+        ],
+        server_addresses
+    );
+}
+
+#[test]
+async fn status_counter() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/unavailable"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_status_counter()
+                .build(),
+        )
+        .build();
+    for path in ["hello", "hello", "unavailable"] {
+        client.get(format!("{url}/{path}")).send().await.unwrap();
+    }
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    for (status, count) in [("200", 2), ("503", 1)] {
+        let (key, _, _, value) = snapshot
+            .iter()
+            .find(|(key, ..)| {
+                key.key().name() == "http.client.response.count"
+                    && key.key().labels().any(|label| label.value() == status)
+            })
+            .unwrap();
+        let labels: Vec<_> = key
+            .key()
+            .labels()
+            .map(|label| (label.key(), label.value()))
+            .collect();
+        assert_eq!(vec![("http.response.status_code", status)], labels);
+        assert_eq!(&DebugValue::Counter(count), value);
+    }
+}
+
+#[test]
+async fn omit_network_protocol_name() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .omit_network_protocol_name()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(!snapshot.is_empty());
+    assert!(snapshot.iter().all(|(key, ..)| key
+        .key()
+        .labels()
+        .all(|label| label.key() != "network.protocol.name")));
+}
+
+#[test]
+async fn omit_url_scheme() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .omit_url_scheme()
+                .enable_active_requests()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot
+        .iter()
+        .all(|(key, ..)| key.key().labels().all(|label| label.key() != "url.scheme")));
+}
+
+#[test]
+async fn last_duration_gauge() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_last_duration_gauge()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let labels = metric_labels(&snapshotter, "http.client.request.last_duration");
+    assert_eq!(
+        vec![("server.address".to_string(), "127.0.0.1".to_string())],
+        labels
+    );
+}
+
+#[test]
+async fn error_type_fn() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .error_type_fn(|res| match res {
+                    Ok(res) if res.status() == 404 => Some("not_found".into()),
+                    Ok(_) => None,
+                    Err(_) => Some("failed".into()),
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/unavailable"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/missing")).send().await.unwrap();
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.contains(&("error.type".into(), "not_found".into())));
+
+    client
+        .get(format!("{url}/unavailable"))
+        .send()
+        .await
+        .unwrap();
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot.iter().any(|(key, ..)| {
+        let labels: Vec<_> = key.key().labels().map(|label| label.key()).collect();
+        key.key().name() == "http.client.request.duration"
+            && labels.contains(&"http.response.status_code")
+            && !labels.contains(&"error.type")
+    }));
+}
+
+#[test]
+async fn outcome_counter() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .enable_outcome_counter()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/unavailable"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+    client
+        .get(format!("{url}/unavailable"))
+        .send()
+        .await
+        .unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let outcomes: Vec<_> = snapshot
+        .iter()
+        .filter(|(key, ..)| key.key().name() == "http.client.request.count")
+        .filter_map(|(key, ..)| {
+            key.key()
+                .labels()
+                .find(|label| label.key() == "outcome")
+                .map(|label| label.value().to_string())
+        })
+        .collect();
+    assert_eq!(vec!["success", "error"], outcomes);
+
+    let labels = metric_labels(&snapshotter, "http.client.request.duration");
+    assert!(labels.iter().all(|(key, _)| key != "outcome"));
+}
+
+#[test]
+async fn metric_prefix() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .metric_prefix("myapp_")
+                .counter_name("requests")
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let names: Vec<_> = snapshot
+        .iter()
+        .map(|(key, unit, ..)| (key.key().name().to_string(), *unit))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                "myapp_http.client.request.duration".to_string(),
+                Some(Unit::Seconds)
+            ),
+            (
+                "myapp_http.client.request.body.size".to_string(),
+                Some(Unit::Bytes)
+            ),
+            (
+                "myapp_http.client.response.body.size".to_string(),
+                Some(Unit::Bytes)
+            ),
+            ("myapp_requests".to_string(), Some(Unit::Count)),
+        ],
+        names
+    );
+}
+
+#[test]
+async fn separate_connect_metrics() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .separate_connect_metrics()
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+    let url = mock_server.uri();
+
+    // wiremock does not support CONNECT requests, but the request is recorded either way
+    let _ = client.request(reqwest::Method::CONNECT, &url).send().await;
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot
+        .iter()
+        .any(|(key, ..)| key.key().name() == "http.client.connect.duration"));
+    assert!(snapshot
+        .iter()
+        .all(|(key, ..)| key.key().name() != "http.client.request.duration"));
+}
+
+#[test]
+async fn custom_sink() {
+    use std::sync::{Arc, Mutex};
+
+    use metrics::{Counter, CounterFn, Gauge, Histogram, HistogramFn, Key};
+
+    /// Captures every recorded value with the name of its metric.
+    #[derive(Clone, Default)]
+    struct CapturingSink(Arc<Mutex<Vec<(String, f64)>>>);
+
+    struct Handle(String, Arc<Mutex<Vec<(String, f64)>>>);
+
+    impl CounterFn for Handle {
+        fn increment(&self, value: u64) {
+            self.1.lock().unwrap().push((self.0.clone(), value as f64));
+        }
+
+        fn absolute(&self, value: u64) {
+            self.increment(value);
+        }
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, value: f64) {
+            self.1.lock().unwrap().push((self.0.clone(), value));
+        }
+    }
+
+    impl MetricsSink for CapturingSink {
+        fn counter(&self, key: &Key) -> Counter {
+            Counter::from_arc(Arc::new(Handle(key.name().to_string(), self.0.clone())))
+        }
+
+        fn gauge(&self, _key: &Key) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn histogram(&self, key: &Key) -> Histogram {
+            Histogram::from_arc(Arc::new(Handle(key.name().to_string(), self.0.clone())))
+        }
+    }
+
+    // No recorder is installed, everything goes through the sink
+    let sink = CapturingSink::default();
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .with_sink(sink.clone())
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("hello world"))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let values = sink.0.lock().unwrap();
+    let names: Vec<_> = values.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        vec![
+            "http.client.request.duration",
+            "http.client.request.body.size",
+            "http.client.response.body.size",
+            "http.client.request.count",
+        ],
+        names
+    );
+    assert_eq!(
+        ("http.client.response.body.size".to_string(), 11.0),
+        values[2]
+    );
+}
+
+#[test]
+async fn head_response_body_size() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("HEAD"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).insert_header(CONTENT_LENGTH, "11"))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddleware::new())
+        .build();
+    client.head(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot
+        .iter()
+        .all(|(key, ..)| key.key().name() != "http.client.response.body.size"));
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .zero_head_response_body_size()
+                .build(),
+        )
+        .build();
+    client.head(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let (_, _, _, value) = snapshot
+        .iter()
+        .find(|(key, ..)| key.key().name() == "http.client.response.body.size")
+        .unwrap();
+    assert_eq!(&DebugValue::Histogram(vec![0.0.into()]), value);
+}
+
+#[test]
+async fn on_complete() {
+    use std::sync::{Arc, Mutex};
+
+    let (_snapshotter, _guard) = install_debug_recorder();
+
+    let completed = Arc::new(Mutex::new(Vec::new()));
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(
+            MetricsMiddlewareBuilder::new()
+                .on_complete({
+                    let completed = Arc::clone(&completed);
+                    move |labels, _duration, res| {
+                        let method = labels
+                            .iter()
+                            .find(|(key, _)| key == "http.request.method")
+                            .map(|(_, value)| value.to_string());
+                        let status = res.as_ref().map(|res| res.status().as_u16()).ok();
+                        completed.lock().unwrap().push((method, status));
+                    }
+                })
+                .build(),
+        )
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    assert_eq!(
+        vec![(Some("GET".to_string()), Some(204))],
+        *completed.lock().unwrap()
+    );
+}
+
+/// Returns the labels of the first recorded metric with the given name.
+fn metric_labels(snapshotter: &Snapshotter, name: &str) -> Vec<(String, String)> {
     snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .find(|(key, ..)| key.key().name() == name)
+        .unwrap_or_else(|| panic!("{name} should be recorded"))
+        .0
+        .key()
+        .labels()
+        .map(|label| (label.key().to_string(), label.value().to_string()))
+        .collect()
 }
@@ -0,0 +1,97 @@
+// Local recorders are scoped to a synchronous closure, so they need a runtime of their own
+#![cfg(not(feature = "disabled"))]
+
+use std::time::Duration;
+
+use metrics_util::debugging::DebuggingRecorder;
+use reqwest_metrics::MetricsMiddleware;
+use reqwest_middleware::{reqwest, ClientBuilder};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+#[test]
+fn with_local_recorder() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mock_server = runtime.block_on(async {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/hello"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+        mock_server
+    });
+
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    metrics::with_local_recorder(&recorder, || {
+        runtime.block_on(async {
+            let client = ClientBuilder::new(reqwest::Client::new())
+                .with(MetricsMiddleware::new())
+                .build();
+            client
+                .get(format!("{}/hello", mock_server.uri()))
+                .send()
+                .await
+                .unwrap();
+        })
+    });
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    for name in [
+        "http.client.request.duration",
+        "http.client.request.body.size",
+        "http.client.response.body.size",
+    ] {
+        assert!(
+            snapshot.iter().any(|(key, ..)| key.key().name() == name),
+            "{name} was not recorded"
+        );
+    }
+}
+
+#[test]
+fn describes_metrics_per_recorder() {
+    let req = reqwest::Client::new()
+        .get("https://example.com")
+        .build()
+        .unwrap();
+    let record = |recorder: &DebuggingRecorder, description: Option<&'static str>| {
+        metrics::with_local_recorder(recorder, || {
+            let mut builder = MetricsMiddleware::builder();
+            if let Some(description) = description {
+                builder.request_duration_description(description);
+            }
+            let res = Ok(reqwest::Response::from(http::Response::new("")));
+            builder
+                .build()
+                .record(&req, &res, Duration::from_millis(10));
+        });
+        recorder
+            .snapshotter()
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .find(|(key, ..)| key.key().name() == "http.client.request.duration")
+            .and_then(|(_, _, description, _)| description)
+            .map(|description| description.to_string())
+    };
+
+    // Both recorders are kept alive, so they cannot share an address
+    let first = DebuggingRecorder::new();
+    let second = DebuggingRecorder::new();
+    let default = Some("Duration of HTTP client requests.".to_string());
+    let custom = Some("Duration of calls to the payments API.".to_string());
+    for recorder in [&first, &second] {
+        assert_eq!(default, record(recorder, None));
+        assert_eq!(
+            custom,
+            record(recorder, Some("Duration of calls to the payments API."))
+        );
+    }
+}
@@ -0,0 +1,47 @@
+#![cfg(not(feature = "disabled"))]
+
+use reqwest_metrics::{test_util::install_debug_recorder, MetricsMiddleware};
+use reqwest_middleware::{reqwest, ClientBuilder};
+use tokio::test;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+#[test]
+async fn install_debug_recorder_records_requests() {
+    let (snapshotter, _guard) = install_debug_recorder();
+
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(MetricsMiddleware::new())
+        .build();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let url = mock_server.uri();
+
+    client.get(format!("{url}/hello")).send().await.unwrap();
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    assert!(snapshot
+        .iter()
+        .any(|(key, ..)| key.key().name() == "http.client.request.duration"));
+}
+
+#[test]
+async fn dropping_the_guard_uninstalls_the_recorder() {
+    let (outer, _outer_guard) = install_debug_recorder();
+    let (inner, inner_guard) = install_debug_recorder();
+    drop(inner_guard);
+
+    metrics::counter!("after_drop").increment(1);
+
+    assert!(inner.snapshot().into_vec().is_empty());
+    assert_eq!(1, outer.snapshot().into_vec().len());
+}